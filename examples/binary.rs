@@ -0,0 +1,106 @@
+use sfsm::*;
+use sfsm_trace::{Decoder, Dictionary};
+use std::cell::RefCell;
+
+// This example requires the trace-binary feature to be enabled to run.
+// Instead of logs, every trace point emits a frame of a few bytes that contains the id of the
+// state machine, the kind of the trace point and the ids of the states and messages. On the host,
+// the frames are turned back into logs with the dictionaries of the state machines and the decoder
+// of the sfsm-trace crate.
+
+thread_local! {
+    // Stands in for the UART the frames would be sent over
+    static UART: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+#[sfsm_trace_binary]
+fn trace(frame: &[u8]) {
+    UART.with(|uart| uart.borrow_mut().extend_from_slice(frame));
+}
+
+struct WaitForLaunch {
+    do_launch: bool,
+}
+struct Launch {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        self.do_launch.into()
+    }
+}
+
+struct StartLaunch {}
+
+add_messages!(
+    Rocket,
+    [
+        StartLaunch -> WaitForLaunch,
+    ]
+);
+
+impl ReceiveMessage<StartLaunch> for WaitForLaunch {
+    fn receive_message(&mut self, _message: StartLaunch) {
+        self.do_launch = true;
+    }
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { do_launch: false })?;
+    PushMessage::<WaitForLaunch, StartLaunch>::push_message(&mut rocket, StartLaunch {})
+        .map_err(|_| SfsmError::Internal)?;
+    rocket.step()?;
+    assert!(IsState::<Launch>::is_state(&rocket));
+    rocket.stop()?;
+
+    // The dictionaries would usually be written to a file by a test or a host build
+    let mut dictionary = Dictionary::new();
+    dictionary.add(Rocket::TRACE_DICTIONARY).unwrap();
+    dictionary.add(Rocket::TRACE_MESSAGES_DICTIONARY).unwrap();
+
+    let frames = UART.with(|uart| uart.borrow().clone());
+    let logs: Vec<String> = Decoder::new().decode(&frames, &dictionary).into_iter()
+        .collect::<Result<_, _>>().unwrap();
+    for log in &logs {
+        println!("{}", log);
+    }
+    println!("{} logs decoded from {} bytes", logs.len(), frames.len());
+
+    assert_eq!(logs, [
+        "Rocket: Start - WaitForLaunch",
+        "Rocket: Push - StartLaunch to WaitForLaunch",
+        "Rocket: Execute - WaitForLaunch",
+        "Rocket: Exit - WaitForLaunch",
+        "Rocket: Transit - From WaitForLaunch to Launch",
+        "Rocket: Enter - Launch",
+        "Rocket: Stop",
+    ]);
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn launch_sequence_with_binary_traces() {
+        run_launch_sequence().unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+use sfsm::*;
+
+// The radio writes the commands of the ground station into a DMA buffer. Instead of copying every
+// frame into an owned buffer, the frame borrows the buffer and is handed to the state as is. The
+// message type is declared with its lifetime like Frame<'a>. The state only decodes what it needs
+// from the frame, as it can not hold on to the borrowed data.
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+}
+
+pub struct WaitForLaunch {
+    countdown: u8,
+}
+pub struct Launch {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        (self.countdown == 0).into()
+    }
+}
+
+#[derive(Debug)]
+pub struct Frame<'a> {
+    bytes: &'a [u8],
+}
+
+add_messages!(
+    Rocket,
+    [
+        Frame<'a> -> WaitForLaunch,
+    ]
+);
+
+impl<'a> ReceiveMessage<Frame<'a>> for WaitForLaunch {
+    fn receive_message(&mut self, message: Frame<'a>) {
+        // A frame holds the command and the countdown
+        if let [b'c', countdown] = message.bytes {
+            self.countdown = *countdown;
+        }
+    }
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { countdown: 10 })?;
+
+    let mut dma_buffer = [0u8; 8];
+    dma_buffer[..2].copy_from_slice(b"c\0");
+    let frame = Frame { bytes: &dma_buffer[..2] };
+    PushMessageAndTransit::<WaitForLaunch, Frame>::push_message_and_transit(&mut rocket, frame).unwrap();
+    assert!(IsState::<Launch>::is_state(&rocket));
+
+    // The buffer can be written again, as the frame did not outlive the push
+    dma_buffer[..2].copy_from_slice(b"c\x05");
+    let frame = Frame { bytes: &dma_buffer[..2] };
+    let rejected = PushMessage::<WaitForLaunch, Frame>::push_message(&mut rocket, frame);
+    assert!(matches!(rejected, Err(MessageError::OtherStateIsActive { message: Frame { bytes: b"c\x05" }, .. })));
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn borrowed_frames() {
+        run_launch_sequence().unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use sfsm::*;
+
+// Some messages are not meant for a single state. The shutdown command must reach the rocket no
+// matter which state is active, and the status can be polled from whichever state reports one.
+// Instead of trying to push the message into every candidate state, the message is broadcast to a
+// list of states or with * to all states. With *, the states that do not receive or return the
+// message are skipped.
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+}
+
+pub struct WaitForLaunch {
+    shutdown: bool,
+}
+pub struct Launch {
+    shutdown: bool,
+}
+pub struct Land {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch, Land],
+    [
+        WaitForLaunch => Launch,
+        Launch => Land,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+derive_state!(Land);
+
+impl From<WaitForLaunch> for Launch {
+    fn from(_: WaitForLaunch) -> Self {
+        Launch { shutdown: false }
+    }
+}
+derive_transition!(WaitForLaunch, Launch, TransitGuard::Transit);
+
+derive_transition_into!(Launch, Land);
+impl Transition<Land> for Launch {
+    fn guard(&self) -> TransitGuard {
+        self.shutdown.into()
+    }
+}
+
+#[derive(Debug)]
+pub struct Shutdown {}
+#[derive(Debug, PartialEq)]
+pub struct Status {
+    shutdown: bool,
+}
+
+add_messages!(
+    Rocket,
+    [
+        Shutdown -> [WaitForLaunch, Launch],  // Only the states that can shut down
+        Status <- *,                          // Every state that reports a status
+    ]
+);
+
+impl ReceiveMessage<Shutdown> for WaitForLaunch {
+    fn receive_message(&mut self, _message: Shutdown) {
+        self.shutdown = true;
+    }
+}
+
+impl ReceiveMessage<Shutdown> for Launch {
+    fn receive_message(&mut self, _message: Shutdown) {
+        self.shutdown = true;
+    }
+}
+
+impl ReturnMessage<Status> for WaitForLaunch {
+    fn return_message(&mut self) -> Option<Status> {
+        Some(Status { shutdown: self.shutdown })
+    }
+}
+
+impl ReturnMessage<Status> for Launch {
+    fn return_message(&mut self) -> Option<Status> {
+        Some(Status { shutdown: self.shutdown })
+    }
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { shutdown: false })?;
+    assert_eq!(PollAnyMessage::<Status>::poll_any_message(&mut rocket).unwrap(), Some(Status { shutdown: false }));
+
+    rocket.step()?;
+    PushAnyMessage::<Shutdown>::push_any_message(&mut rocket, Shutdown {}).unwrap();
+    assert_eq!(PollAnyMessage::<Status>::poll_any_message(&mut rocket).unwrap(), Some(Status { shutdown: true }));
+
+    // Land is not one of the states the shutdown is pushed into. It is skipped when the status is
+    // polled from all states, as it does not return one.
+    rocket.step()?;
+    let land = RocketStates::LandState(None).state_id();
+    let shutdown = PushAnyMessage::<Shutdown>::push_any_message(&mut rocket, Shutdown {});
+    assert!(matches!(shutdown, Err(MessageError::OtherStateIsActive { active_state, .. }) if active_state == land));
+    let status = PollAnyMessage::<Status>::poll_any_message(&mut rocket);
+    assert!(matches!(status, Err(MessageError::NotAccepted { active_state, .. }) if active_state == land));
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn broadcast_shutdown() {
+        run_launch_sequence().unwrap();
+    }
+}
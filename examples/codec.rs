@@ -0,0 +1,120 @@
+use sfsm::*;
+use serde::{Deserialize, Serialize};
+
+// This example requires the codec feature to be enabled to run.
+// The commands of the ground station arrive over a serial link as frames. Each frame starts with
+// the tag of the message, followed by the message serialized with postcard. Instead of
+// parsing the tag and calling the right push_message by hand, the frame is pushed as is with
+// push_frame. The status of the rocket is polled and encoded into a frame with poll_frame.
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+}
+
+pub struct WaitForLaunch {
+    countdown: u8,
+}
+pub struct Launch {
+    altitude: u32,
+}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+impl State for WaitForLaunch {
+    fn execute(&mut self) {
+        self.countdown = self.countdown.saturating_sub(1);
+    }
+}
+impl State for Launch {
+    fn execute(&mut self) {
+        self.altitude += 500;
+    }
+}
+
+impl From<WaitForLaunch> for Launch {
+    fn from(_: WaitForLaunch) -> Self {
+        Launch { altitude: 0 }
+    }
+}
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        (self.countdown == 0).into()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SetCountdown {
+    steps: u8,
+}
+#[derive(Serialize)]
+pub struct Status {
+    altitude: u32,
+}
+
+// The codec is only generated for the messages declared with #[sfsm(codec)]. The encoded messages
+// are tagged in the order they were declared, unless their tag is pinned. Pinned tags stay the same
+// if messages are added later on.
+add_messages!(
+    #[sfsm(codec)]
+    Rocket,
+    [
+        SetCountdown -> WaitForLaunch,      // Tag 0
+        #[sfsm(tag = 8)] Status <- Launch,  // Tag 8
+    ]
+);
+
+impl ReceiveMessage<SetCountdown> for WaitForLaunch {
+    fn receive_message(&mut self, message: SetCountdown) {
+        self.countdown = message.steps;
+    }
+}
+
+impl ReturnMessage<Status> for Launch {
+    fn return_message(&mut self) -> Option<Status> {
+        Some(Status { altitude: self.altitude })
+    }
+}
+
+fn run_launch_sequence() -> Result<Vec<Vec<u8>>, SfsmError> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { countdown: 10 })?;
+
+    // A frame with an unknown tag is rejected before anything is pushed
+    assert!(matches!(rocket.push_frame(&[7]), Err(FrameError::Codec(CodecError::UnknownTag(7)))));
+
+    // Shorten the countdown to a single step
+    assert!(rocket.push_frame(&[0, 1]).is_ok());
+
+    let mut sent = vec![];
+    let mut buffer = [0u8; 8];
+    for _ in 0..3 {
+        rocket.step()?;
+        if let Some(frame) = rocket.poll_frame(&mut buffer).unwrap() {
+            sent.push(frame.to_vec());
+        }
+    }
+    Ok(sent)
+}
+
+fn main() {
+    println!("{:?}", run_launch_sequence().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn encoded_status() {
+        // The altitudes are encoded as varints, so 500 and 1000 take two bytes
+        assert_eq!(run_launch_sequence().unwrap(), vec![vec![8, 0], vec![8, 0xf4, 0x03], vec![8, 0xe8, 0x07]]);
+    }
+}
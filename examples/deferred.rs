@@ -0,0 +1,98 @@
+use sfsm::*;
+
+// The ground station might send the abort command while the rocket is still counting down. The
+// Launch state, which handles the abort, is not active yet, so a normal push would fail. The
+// #[sfsm(defer)] marker holds the command in the state machine instead, and it is delivered right
+// after the Launch state has been entered. The state machine holds up to the number of deferred
+// messages given with #[sfsm(defer = N)].
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+}
+
+pub struct WaitForLaunch {
+    countdown: u8,
+}
+pub struct Launch {
+    aborted: bool,
+}
+
+add_state_machine!(
+    #[sfsm(defer = 1)]
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+impl State for WaitForLaunch {
+    fn execute(&mut self) {
+        self.countdown -= 1;
+    }
+}
+derive_state!(Launch);
+
+impl From<WaitForLaunch> for Launch {
+    fn from(_: WaitForLaunch) -> Self {
+        Launch { aborted: false }
+    }
+}
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        (self.countdown == 0).into()
+    }
+}
+
+#[derive(Debug)]
+pub struct Abort {}
+
+add_messages!(
+    #[sfsm(dispatch)]
+    Rocket,
+    [
+        #[sfsm(defer)] Abort -> Launch,
+    ]
+);
+
+impl ReceiveMessage<Abort> for Launch {
+    fn receive_message(&mut self, _message: Abort) {
+        self.aborted = true;
+    }
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { countdown: 2 })?;
+
+    // The abort is held, as the rocket is still counting down. No more aborts fit in.
+    PushMessage::<Launch, Abort>::push_message(&mut rocket, Abort {}).unwrap();
+    let full = PushMessage::<Launch, Abort>::push_message(&mut rocket, Abort {});
+    assert!(matches!(full, Err(MessageError::QueueIsFull(Abort {}))));
+
+    rocket.step()?;
+    rocket.step()?;
+
+    // The rocket entered Launch in the last step and received the abort right after
+    match rocket.stop()? {
+        RocketStates::LaunchState(Some(launch)) => assert!(launch.aborted),
+        _ => panic!("The rocket must have launched"),
+    }
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn deferred_abort() {
+        run_launch_sequence().unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+use sfsm::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// This example requires the trace-defmt feature to be enabled to run.
+// The DEFMT_LOG environment variable decides which levels are compiled in. Transitions are
+// logged with the debug level and steps with the trace level, so run the example with:
+// DEFMT_LOG=trace cargo run --example defmt --features="trace-defmt trace-steps"
+
+/// Capture the frames instead of sending them to a probe. On a real target, use a logger such as
+/// defmt-rtt and decode the frames on the host. The sfsm-defmt-test crate decodes the frames of
+/// this launch sequence with the defmt table.
+static FRAMES: AtomicUsize = AtomicUsize::new(0);
+static ENCODER: Mutex<defmt::Encoder> = Mutex::new(defmt::Encoder::new());
+static CAPTURED: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+fn capture(bytes: &[u8]) {
+    CAPTURED.lock().unwrap().extend_from_slice(bytes);
+}
+
+#[defmt::global_logger]
+struct Logger;
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        FRAMES.fetch_add(1, Ordering::SeqCst);
+        ENCODER.lock().unwrap().start_frame(capture);
+    }
+    unsafe fn flush() {}
+    unsafe fn release() {
+        ENCODER.lock().unwrap().end_frame(capture);
+    }
+    unsafe fn write(bytes: &[u8]) {
+        ENCODER.lock().unwrap().write(bytes, capture);
+    }
+}
+
+defmt::timestamp!("{=usize}", FRAMES.load(Ordering::SeqCst));
+
+struct WaitForLaunch {
+    do_launch: bool,
+}
+struct Launch {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        self.do_launch.into()
+    }
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let mut rocket = Rocket::new();
+
+    rocket.start(WaitForLaunch { do_launch: true })?;
+    let frames_after_start = FRAMES.load(Ordering::SeqCst);
+    assert!(frames_after_start > 0);
+
+    rocket.step()?;
+    assert!(IsState::<Launch>::is_state(&rocket));
+    assert!(FRAMES.load(Ordering::SeqCst) > frames_after_start);
+
+    rocket.stop()?;
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+    let bytes = CAPTURED.lock().unwrap().len();
+    println!("Emitted {} frames with {} bytes", FRAMES.load(Ordering::SeqCst), bytes);
+}
@@ -0,0 +1,146 @@
+use sfsm::*;
+use std::marker::PhantomData;
+
+// The commands of the ground station arrive as bytes over the radio. They are decoded into the
+// generated RocketMessages enum and pushed with a single call, no matter which state receives
+// them. In the same way, whatever the active state has to report is polled with poll_any and sent
+// back. The enum is generated with the dispatch attribute, and the other attributes of the
+// add_messages! macro are added to it.
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+}
+
+pub struct WaitForLaunch {
+    do_launch: bool,
+}
+pub struct Launch {
+    do_abort: bool,
+}
+pub struct Abort {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch, Abort],
+    [
+        WaitForLaunch => Launch,
+        Launch => Abort,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+derive_state!(Abort);
+
+impl From<WaitForLaunch> for Launch {
+    fn from(_: WaitForLaunch) -> Self {
+        Launch { do_abort: false }
+    }
+}
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        self.do_launch.into()
+    }
+}
+
+derive_transition_into!(Launch, Abort);
+impl Transition<Abort> for Launch {
+    fn guard(&self) -> TransitGuard {
+        self.do_abort.into()
+    }
+}
+
+#[derive(Debug)]
+pub struct Start {}
+#[derive(Debug)]
+pub struct Stop {}
+#[derive(Debug)]
+pub struct Command<T> {
+    phantom: PhantomData<T>,
+}
+#[derive(Debug, PartialEq)]
+pub struct Status {
+    code: u8,
+}
+
+add_messages!(
+    #[sfsm(dispatch)]
+    #[derive(Debug)]
+    Rocket,
+    [
+        Command<Start> -> WaitForLaunch,
+        Command<Stop> -> Launch,
+        Status <- Launch,
+        Status <- Abort,
+    ]
+);
+
+impl ReceiveMessage<Command<Start>> for WaitForLaunch {
+    fn receive_message(&mut self, _message: Command<Start>) {
+        self.do_launch = true;
+    }
+}
+
+impl ReceiveMessage<Command<Stop>> for Launch {
+    fn receive_message(&mut self, _message: Command<Stop>) {
+        self.do_abort = true;
+    }
+}
+
+impl ReturnMessage<Status> for Launch {
+    fn return_message(&mut self) -> Option<Status> {
+        Some(Status { code: 1 })
+    }
+}
+
+impl ReturnMessage<Status> for Abort {
+    fn return_message(&mut self) -> Option<Status> {
+        Some(Status { code: 2 })
+    }
+}
+
+// Decodes the commands of the radio into the messages of the state machine
+fn decode(byte: u8) -> Option<RocketMessages> {
+    match byte {
+        b's' => Some(RocketMessages::CommandStart(Command { phantom: PhantomData })),
+        b'x' => Some(RocketMessages::CommandStop(Command { phantom: PhantomData })),
+        _ => None,
+    }
+}
+
+fn run_launch_sequence() -> Result<Vec<u8>, SfsmError> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { do_launch: false })?;
+
+    let mut reports = vec![];
+    for byte in b"sx" {
+        if let Some(message) = decode(*byte) {
+            rocket.push(message).unwrap();
+        }
+        rocket.step()?;
+        if let Some(RocketMessages::Status(status)) = rocket.poll_any() {
+            reports.push(status.code);
+        }
+    }
+
+    // The stop command is rejected, as the rocket has already aborted
+    let rejected = rocket.push(decode(b'x').unwrap());
+    assert!(matches!(rejected, Err(MessageError::OtherStateIsActive { message: RocketMessages::CommandStop(_), .. })));
+    Ok(reports)
+}
+
+fn main() {
+    println!("{:?}", run_launch_sequence().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn dispatched_launch_sequence() {
+        assert_eq!(run_launch_sequence().unwrap(), [1, 2]);
+    }
+}
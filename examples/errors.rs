@@ -0,0 +1,134 @@
+use sfsm::*;
+use std::cell::RefCell;
+use std::fmt;
+
+// This example requires the trace-errors feature to be enabled to run.
+// When a fallible state machine enters the error state, the trace tells which state and which of
+// its functions failed and with what error. The error is formatted with its TraceFormat
+// implementation if there is one and with Debug otherwise.
+
+thread_local! {
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+    LOGS.with(|logs| logs.borrow_mut().push(log.to_string()));
+}
+
+/// Returns the logs of the error state entries
+fn error_logs() -> Vec<String> {
+    LOGS.with(|logs| logs.borrow().iter().filter(|log| log.contains("error state")).cloned().collect())
+}
+
+pub struct WaitForLaunch {
+    ignition: bool,
+}
+pub struct Launch {
+    ignition: bool,
+}
+pub struct HandleMalfunction {}
+
+// The error does not implement Debug, so it must implement TraceFormat to be traced
+pub enum RocketMalfunction {
+    IgnitionFailed,
+    BoostersFellOff(u8),
+}
+
+impl TraceFormat for RocketMalfunction {
+    fn trace_format(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RocketMalfunction::IgnitionFailed => write!(f, "Ignition failed"),
+            RocketMalfunction::BoostersFellOff(count) => write!(f, "{} boosters fell off", count),
+        }
+    }
+}
+
+add_fallible_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch, HandleMalfunction],
+    [
+        WaitForLaunch => Launch,
+        HandleMalfunction => WaitForLaunch
+    ],
+    RocketMalfunction,
+    HandleMalfunction
+);
+
+impl TryState for WaitForLaunch {
+    type Error = RocketMalfunction;
+}
+
+impl TryState for Launch {
+    type Error = RocketMalfunction;
+    fn try_entry(&mut self) -> Result<(), Self::Error> {
+        if self.ignition {
+            Ok(())
+        } else {
+            Err(RocketMalfunction::IgnitionFailed)
+        }
+    }
+    fn try_execute(&mut self) -> Result<(), Self::Error> {
+        Err(RocketMalfunction::BoostersFellOff(2))
+    }
+}
+
+impl TryState for HandleMalfunction {
+    type Error = RocketMalfunction;
+}
+
+impl TryErrorState for HandleMalfunction {
+    fn consume_error(&mut self, _err: Self::Error) {}
+}
+
+impl From<WaitForLaunch> for Launch {
+    fn from(state: WaitForLaunch) -> Self {
+        Launch { ignition: state.ignition }
+    }
+}
+derive_try_transition!(WaitForLaunch, Launch, TransitGuard::Transit);
+
+impl From<HandleMalfunction> for WaitForLaunch {
+    fn from(_: HandleMalfunction) -> Self {
+        WaitForLaunch { ignition: true }
+    }
+}
+derive_try_transition!(HandleMalfunction, WaitForLaunch, TransitGuard::Remain);
+
+derive_transition_into!(WaitForLaunch, HandleMalfunction);
+derive_transition_into!(Launch, HandleMalfunction);
+
+fn run_launch_sequence() -> Result<(), ExtendedSfsmError<RocketMalfunction>> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { ignition: false })?;
+    rocket.step()?;
+    assert!(IsState::<HandleMalfunction>::is_state(&rocket));
+
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { ignition: true })?;
+    rocket.step()?;
+    rocket.step()?;
+    assert!(IsState::<HandleMalfunction>::is_state(&rocket));
+
+    assert_eq!(error_logs(), [
+        "Rocket: Enter error state - From Launch, entry failed: Ignition failed",
+        "Rocket: Enter error state - From Launch, execute failed: 2 boosters fell off",
+    ]);
+    Ok(())
+}
+
+fn main() {
+    assert!(run_launch_sequence().is_ok());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn launch_sequence_with_errors() {
+        assert!(run_launch_sequence().is_ok());
+    }
+}
@@ -0,0 +1,134 @@
+use sfsm::*;
+
+// The ground station sends the rocket a target altitude. An altitude out of range can not be flown,
+// so the Launch state rejects it by returning an error from try_receive_message. The message is
+// marked with #[sfsm(fallible)] and the rejection is handled like any other failure of a fallible
+// state machine: the rocket enters the error state right away.
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+}
+
+pub struct WaitForLaunch {}
+pub struct Launch {
+    altitude: u32,
+}
+pub struct HandleMalfunction {}
+
+#[derive(Debug)]
+pub enum RocketMalfunction {
+    AltitudeOutOfRange(u32),
+}
+
+add_fallible_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch, HandleMalfunction],
+    [
+        WaitForLaunch => Launch,
+        HandleMalfunction => WaitForLaunch
+    ],
+    RocketMalfunction,
+    HandleMalfunction
+);
+
+impl TryState for WaitForLaunch {
+    type Error = RocketMalfunction;
+}
+
+impl TryState for Launch {
+    type Error = RocketMalfunction;
+}
+
+impl TryState for HandleMalfunction {
+    type Error = RocketMalfunction;
+}
+
+impl TryErrorState for HandleMalfunction {
+    fn consume_error(&mut self, _err: Self::Error) {}
+}
+
+impl From<WaitForLaunch> for Launch {
+    fn from(_: WaitForLaunch) -> Self {
+        Launch { altitude: 0 }
+    }
+}
+derive_try_transition!(WaitForLaunch, Launch, TransitGuard::Transit);
+
+derive_transition_into!(HandleMalfunction, WaitForLaunch);
+derive_try_transition!(HandleMalfunction, WaitForLaunch, TransitGuard::Transit);
+
+derive_transition_into!(WaitForLaunch, HandleMalfunction);
+derive_transition_into!(Launch, HandleMalfunction);
+
+#[derive(Debug)]
+pub struct TargetAltitude {
+    meters: u32,
+}
+
+add_messages!(
+    Rocket,
+    [
+        #[sfsm(fallible)] TargetAltitude -> Launch,
+    ]
+);
+
+impl TryReceiveMessage<TargetAltitude> for Launch {
+    fn try_receive_message(&mut self, message: TargetAltitude) -> Result<(), Self::Error> {
+        if message.meters > 100_000 {
+            return Err(RocketMalfunction::AltitudeOutOfRange(message.meters));
+        }
+        self.altitude = message.meters;
+        Ok(())
+    }
+}
+
+fn run_launch_sequence() -> Result<(), ExtendedSfsmError<RocketMalfunction>> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch {})?;
+
+    // The target can not be set before the launch
+    let rejected = TryPushMessage::<Launch, TargetAltitude>::try_push_message(&mut rocket, TargetAltitude { meters: 1000 });
+    assert!(matches!(rejected, Err(PushTransitError::Message(MessageError::OtherStateIsActive { .. }))));
+
+    rocket.step()?;
+    TryPushMessage::<Launch, TargetAltitude>::try_push_message(&mut rocket, TargetAltitude { meters: 1000 }).unwrap();
+    assert!(IsState::<Launch>::is_state(&rocket));
+
+    // The altitude is out of range and the rocket handles the malfunction without a step
+    TryPushMessage::<Launch, TargetAltitude>::try_push_message(&mut rocket, TargetAltitude { meters: 200_000 }).unwrap();
+    assert!(IsState::<HandleMalfunction>::is_state(&rocket));
+    Ok(())
+}
+
+fn abort_launch() -> Result<(), ExtendedSfsmError<RocketMalfunction>> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch {})?;
+    rocket.step()?;
+
+    // The transitions of the error state are checked right after the rejection, so the rocket is
+    // back to waiting for the launch without a step
+    TryPushMessageAndTransit::<Launch, TargetAltitude>::try_push_message_and_transit(&mut rocket, TargetAltitude { meters: 200_000 }).unwrap();
+    assert!(IsState::<WaitForLaunch>::is_state(&rocket));
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{abort_launch, run_launch_sequence};
+
+    #[test]
+    fn rejected_altitude() {
+        run_launch_sequence().unwrap();
+    }
+
+    #[test]
+    fn rejected_altitude_with_transit() {
+        abort_launch().unwrap();
+    }
+}
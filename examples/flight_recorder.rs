@@ -0,0 +1,169 @@
+use sfsm::*;
+use std::sync::Mutex;
+
+// This example requires the trace-record feature to be enabled to run.
+// The state machines pass a record of every transition, error state entry and message to the
+// function registered with #[sfsm_record]. The flight recorder keeps the last few of them, so
+// they can be dumped once a fault occurred. On a no_std target, a critical section mutex can be
+// used instead of the std mutex.
+
+static RECORDER: Mutex<FlightRecorder<6>> = Mutex::new(FlightRecorder::new());
+
+#[sfsm_record]
+fn record(record: &TraceRecord) {
+    RECORDER.lock().unwrap().record(record);
+}
+
+/// Formats the records, starting with the oldest one.
+fn dump() -> Vec<String> {
+    match RECORDER.try_lock() {
+        Ok(recorder) => recorder.iter().map(|record| format!("{}", record)).collect(),
+        Err(_) => vec![],
+    }
+}
+
+pub struct WaitForLaunch {
+    do_launch: bool,
+}
+pub struct Launch {}
+pub struct HandleMalfunction {}
+
+#[derive(Debug)]
+pub enum RocketMalfunction {
+    BoostersFellOff,
+}
+
+add_fallible_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch, HandleMalfunction],
+    [
+        WaitForLaunch => Launch,
+        HandleMalfunction => WaitForLaunch
+    ],
+    RocketMalfunction,
+    HandleMalfunction
+);
+
+impl TryState for WaitForLaunch {
+    type Error = RocketMalfunction;
+}
+
+impl TryState for Launch {
+    type Error = RocketMalfunction;
+    fn try_execute(&mut self) -> Result<(), Self::Error> {
+        Err(RocketMalfunction::BoostersFellOff)
+    }
+}
+
+impl TryState for HandleMalfunction {
+    type Error = RocketMalfunction;
+}
+
+impl TryErrorState for HandleMalfunction {
+    fn consume_error(&mut self, _err: Self::Error) {}
+}
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl TryTransition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        self.do_launch.into()
+    }
+}
+
+impl From<HandleMalfunction> for WaitForLaunch {
+    fn from(_: HandleMalfunction) -> Self {
+        WaitForLaunch { do_launch: false }
+    }
+}
+derive_try_transition!(HandleMalfunction, WaitForLaunch, TransitGuard::Transit);
+
+derive_transition_into!(WaitForLaunch, HandleMalfunction);
+derive_transition_into!(Launch, HandleMalfunction);
+
+// A second state machine that shares the flight recorder
+pub struct Listening {}
+
+add_state_machine!(
+    Radio,
+    Listening,
+    [Listening],
+    []
+);
+
+derive_state!(Listening);
+
+#[derive(Debug)]
+pub struct Command {}
+pub struct Status {}
+
+add_messages!(
+    Radio,
+    [
+        Command -> Listening,
+        Status <- Listening,
+    ]
+);
+
+impl ReceiveMessage<Command> for Listening {
+    fn receive_message(&mut self, _message: Command) {}
+}
+
+impl ReturnMessage<Status> for Listening {
+    fn return_message(&mut self) -> Option<Status> {
+        Some(Status {})
+    }
+}
+
+fn run_launch_sequence() -> Result<(), ExtendedSfsmError<RocketMalfunction>> {
+    let mut rocket = Rocket::new();
+    let mut radio = Radio::new();
+    radio.start(Listening {}).unwrap();
+    rocket.start(WaitForLaunch { do_launch: true })?;
+
+    PushMessage::<Listening, Command>::push_message(&mut radio, Command {}).unwrap();
+    PollMessage::<Listening, Status>::poll_message(&mut radio).unwrap();
+
+    rocket.step()?;
+    // The boosters fall off during this step
+    rocket.step()?;
+    rocket.step()?;
+    assert!(IsState::<WaitForLaunch>::is_state(&rocket));
+
+    rocket.stop()?;
+    Ok(())
+}
+
+fn main() {
+    // Dump the last records if anything goes wrong
+    std::panic::set_hook(Box::new(|info| {
+        eprintln!("{}", info);
+        for record in dump() {
+            eprintln!("{}", record);
+        }
+    }));
+
+    run_launch_sequence().unwrap();
+    for record in dump() {
+        println!("{}", record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{run_launch_sequence, dump};
+
+    #[test]
+    fn launch_sequence_with_flight_recorder() {
+        run_launch_sequence().unwrap();
+        // Only the last six records are kept
+        assert_eq!(dump(), [
+            "Radio #0: Push - Command to Listening",
+            "Radio #0: Poll - Status from Listening",
+            "Rocket #1: Transit - From WaitForLaunch to Launch",
+            "Rocket #2: Enter error state - From Launch, execute failed",
+            "Rocket #3: Transit - From HandleMalfunction to WaitForLaunch",
+            "Rocket #3: Stop - WaitForLaunch",
+        ]);
+    }
+}
@@ -0,0 +1,93 @@
+use sfsm::*;
+use std::cell::RefCell;
+
+// This example requires the trace-guards feature to be enabled to run.
+// Every evaluated guard is traced together with its result. This shows which guard kept the state
+// machine in a state without having to add prints to the guards.
+
+thread_local! {
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+    LOGS.with(|logs| logs.borrow_mut().push(log.to_string()));
+}
+
+/// Returns the guard logs. The other trace features might be enabled as well.
+fn guard_logs() -> Vec<String> {
+    LOGS.with(|logs| logs.borrow().iter().filter(|log| log.contains("Guard")).cloned().collect())
+}
+
+struct WaitForLaunch {
+    fuel: u8,
+    malfunction: bool,
+}
+struct Launch {}
+struct Abort {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch, Abort],
+    [
+        WaitForLaunch => Abort,
+        WaitForLaunch => Launch,
+    ]
+);
+
+impl State for WaitForLaunch {
+    fn execute(&mut self) {
+        self.fuel += 50;
+    }
+}
+derive_state!(Launch);
+derive_state!(Abort);
+
+derive_transition_into!(WaitForLaunch, Abort);
+impl Transition<Abort> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        self.malfunction.into()
+    }
+}
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        (self.fuel >= 100).into()
+    }
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { fuel: 0, malfunction: false })?;
+
+    // Not enough fuel yet
+    rocket.step()?;
+    assert!(IsState::<WaitForLaunch>::is_state(&rocket));
+    rocket.step()?;
+    assert!(IsState::<Launch>::is_state(&rocket));
+
+    assert_eq!(guard_logs(), [
+        "Rocket: Guard - From WaitForLaunch to Abort: Remain",
+        "Rocket: Guard - From WaitForLaunch to Launch: Remain",
+        "Rocket: Guard - From WaitForLaunch to Abort: Remain",
+        "Rocket: Guard - From WaitForLaunch to Launch: Transit",
+    ]);
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn launch_sequence_with_guards() {
+        run_launch_sequence().unwrap();
+    }
+}
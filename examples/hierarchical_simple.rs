@@ -1,4 +1,5 @@
 use sfsm::*;
+use std::cell::RefCell;
 
 // An example of how a hierarchical state machine can be built
 pub struct Offline {}
@@ -17,8 +18,10 @@ add_state_machine!(
     ]
 );
 
-// Defines the Online inner state machine.
+// Defines the Online inner state machine. The parent attribute prefixes its traces with the
+// path of the outer state machine, like "ForwardObserver/Online: Enter - Requesting".
 add_state_machine!(
+    #[sfsm(parent = "ForwardObserver")]
     Online,
     Standby,
     [Standby, Requesting, Observing, Reporting],
@@ -122,6 +125,11 @@ impl From<Offline> for Online {
 #[sfsm_trace]
 fn trace(log: &str) {
     println!("{}", log);
+    LOGS.with(|logs| logs.borrow_mut().push(log.to_string()));
+}
+
+thread_local! {
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
 }
 
 fn run_hierarchical_simple() -> Result<(), SfsmError> {
@@ -151,6 +159,13 @@ fn run_hierarchical_simple() -> Result<(), SfsmError> {
     forward_observer.step()?;
     assert!(IsState::<Online>::is_state(&forward_observer));
 
+    // The traces of the inner state machine carry the path of the outer one
+    if cfg!(feature = "trace") {
+        let logs = LOGS.with(|logs| logs.borrow().clone());
+        assert!(logs.contains(&"ForwardObserver: Transit - From Offline to Online".to_string()));
+        assert!(logs.contains(&"ForwardObserver/Online: Transit - From Standby to Requesting".to_string()));
+    }
+
     Ok(())
 }
 
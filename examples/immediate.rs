@@ -0,0 +1,84 @@
+use sfsm::*;
+
+// The rocket only steps every few seconds, but the launch command must be acted on right away.
+// Instead of waiting for the next step, the command is pushed with push_message_and_transit. The
+// transitions of the receiving state are checked right after the message has been received,
+// without executing the state.
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+}
+
+pub struct WaitForLaunch {
+    do_launch: bool,
+    countdown: u8,
+}
+pub struct Launch {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+impl State for WaitForLaunch {
+    fn execute(&mut self) {
+        self.countdown -= 1;
+    }
+}
+derive_state!(Launch);
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        self.do_launch.into()
+    }
+}
+
+#[derive(Debug)]
+pub struct StartLaunch {}
+
+add_messages!(
+    Rocket,
+    [
+        StartLaunch -> WaitForLaunch,
+    ]
+);
+
+impl ReceiveMessage<StartLaunch> for WaitForLaunch {
+    fn receive_message(&mut self, _message: StartLaunch) {
+        self.do_launch = true;
+    }
+}
+
+fn run_launch_sequence() -> Result<(), PushTransitError<StartLaunch, SfsmError>> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { do_launch: false, countdown: 10 }).map_err(PushTransitError::Transit)?;
+
+    // The rocket launches without a step in between
+    PushMessageAndTransit::<WaitForLaunch, StartLaunch>::push_message_and_transit(&mut rocket, StartLaunch {})?;
+    assert!(IsState::<Launch>::is_state(&rocket));
+
+    // The command can not be pushed anymore, as the rocket is already launching
+    let rejected = PushMessageAndTransit::<WaitForLaunch, StartLaunch>::push_message_and_transit(&mut rocket, StartLaunch {});
+    assert!(matches!(rejected, Err(PushTransitError::Message(MessageError::OtherStateIsActive { .. }))));
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn immediate_launch() {
+        run_launch_sequence().unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+use sfsm::*;
+use log::{Log, Metadata, Record, LevelFilter};
+use std::sync::Mutex;
+
+// This example requires the trace-log feature to be enabled to run.
+// Transitions and messages are logged with the debug level and steps with the trace level.
+
+/// A logger that stores the logs instead of printing them. Any other logger implementation,
+/// like env_logger or simple_logger, can be used instead.
+struct Logger {
+    logs: Mutex<Vec<String>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let log = format!("{}", record.args());
+        println!("{} {}", record.level(), log);
+        self.logs.lock().unwrap().push(log);
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger { logs: Mutex::new(Vec::new()) };
+
+struct WaitForLaunch {
+    do_launch: bool,
+}
+struct Launch {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        self.do_launch.into()
+    }
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    // Ignore the error if the logger has already been set by another test
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(LevelFilter::Trace);
+
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { do_launch: true })?;
+    rocket.step()?;
+    assert!(IsState::<Launch>::is_state(&rocket));
+    rocket.stop()?;
+
+    let logs = LOGGER.logs.lock().unwrap();
+    assert!(logs.contains(&"Rocket: Start - WaitForLaunch".to_string()));
+    assert!(logs.contains(&"Rocket: Transit - From WaitForLaunch to Launch".to_string()));
+    assert!(logs.contains(&"Rocket: Stop".to_string()));
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn launch_sequence_with_log() {
+        run_launch_sequence().unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+use sfsm::*;
+
+// Instead of a separate add_messages! macro, the messages can be declared as the last list of
+// add_state_machine!. The states the messages are passed to and from are then checked against the
+// states of the state machine, so a state that is not part of it is reported right where it was
+// written.
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+}
+
+pub struct WaitForLaunch {
+    do_launch: bool,
+}
+pub struct Launch {}
+
+#[derive(Debug)]
+pub struct StartLaunch {}
+#[derive(Debug, PartialEq)]
+pub struct Status {
+    launched: bool,
+}
+
+add_state_machine!(
+    #[sfsm(dispatch)]
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ],
+    [
+        StartLaunch -> WaitForLaunch,
+        Status <- *,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        self.do_launch.into()
+    }
+}
+
+impl ReceiveMessage<StartLaunch> for WaitForLaunch {
+    fn receive_message(&mut self, _message: StartLaunch) {
+        self.do_launch = true;
+    }
+}
+
+impl ReturnMessage<Status> for WaitForLaunch {
+    fn return_message(&mut self) -> Option<Status> {
+        Some(Status { launched: false })
+    }
+}
+
+impl ReturnMessage<Status> for Launch {
+    fn return_message(&mut self) -> Option<Status> {
+        Some(Status { launched: true })
+    }
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { do_launch: false })?;
+
+    assert!(rocket.push(RocketMessages::StartLaunch(StartLaunch {})).is_ok());
+    rocket.step()?;
+    assert_eq!(PollAnyMessage::<Status>::poll_any_message(&mut rocket).unwrap(), Some(Status { launched: true }));
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn declared_messages() {
+        run_launch_sequence().unwrap();
+    }
+}
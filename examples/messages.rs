@@ -109,6 +109,10 @@ fn run_launch_sequence_with_message() -> Result<(), SfsmError> {
 
     let mut rocket = Rocket::new();
 
+    // Messages can not be pushed before the state machine has been started
+    let result = PushMessage::<WaitForLaunch, StartLaunch>::push_message(&mut rocket, StartLaunch {start: true});
+    assert!(matches!(result, Err(MessageError::NotStarted(_))));
+
     let wait_for_launch = WaitForLaunch {
         malfunction: false,
         do_launch: false,
@@ -125,14 +129,12 @@ fn run_launch_sequence_with_message() -> Result<(), SfsmError> {
     assert!(IsState::<Abort>::is_state(&rocket));
 
     // Telling the state machine to start the lift off while not in the WaitForLaunch state will result in an error
-    let result = PushMessage::<WaitForLaunch, StartLaunch>::push_message(&mut rocket, StartLaunch {start: true});
-    assert!(result.is_err());
+    let err = PushMessage::<WaitForLaunch, StartLaunch>::push_message(&mut rocket, StartLaunch {start: true}).unwrap_err();
+    assert!(matches!(err, MessageError::OtherStateIsActive { .. }));
+    // The error also tells which state was active instead, which is Abort
+    assert_eq!(err.active_state(), Some(RocketStates::AbortState(None).state_id()));
     // But it allows us to return the lost message and do something else with it
-    if let Err(start_result) = result {
-        if let MessageError::StateIsNotActive(start) = start_result {
-            assert!(start.start)
-        }
-    }
+    assert!(err.into_message().start);
 
     rocket.step()?;
     assert!(IsState::<WaitForLaunch>::is_state(&rocket));
@@ -0,0 +1,89 @@
+use sfsm::*;
+
+// The rocket and its telemetry are written by different teams, so the telemetry messages are
+// declared in a module of their own. To poll the status from all states with *, add_messages! asks
+// the state machine for its states. This works wherever the state machine is imported, even in a
+// module that comes before the state machine and with add_messages! called by its path.
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+}
+
+mod rocket {
+    use super::*;
+
+    pub mod telemetry {
+        // Imports the state machine together with everything else of the parent module
+        use super::*;
+
+        #[derive(Debug, PartialEq)]
+        pub struct Status {
+            pub launched: bool,
+        }
+
+        sfsm::add_messages!(
+            Rocket,
+            [
+                Status <- *,
+            ]
+        );
+
+        impl ReturnMessage<Status> for WaitForLaunch {
+            fn return_message(&mut self) -> Option<Status> {
+                Some(Status { launched: false })
+            }
+        }
+
+        impl ReturnMessage<Status> for Launch {
+            fn return_message(&mut self) -> Option<Status> {
+                Some(Status { launched: true })
+            }
+        }
+    }
+
+    pub struct WaitForLaunch {}
+    pub struct Launch {}
+
+    add_state_machine!(
+        pub Rocket,
+        WaitForLaunch,
+        [WaitForLaunch, Launch],
+        [
+            WaitForLaunch => Launch,
+        ]
+    );
+
+    derive_state!(WaitForLaunch);
+    derive_state!(Launch);
+
+    derive_transition_into!(WaitForLaunch, Launch);
+    derive_transition!(WaitForLaunch, Launch, TransitGuard::Transit);
+}
+
+use rocket::{Rocket, WaitForLaunch};
+use rocket::telemetry::Status;
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch {})?;
+    assert_eq!(PollAnyMessage::<Status>::poll_any_message(&mut rocket).unwrap(), Some(Status { launched: false }));
+
+    rocket.step()?;
+    assert_eq!(PollAnyMessage::<Status>::poll_any_message(&mut rocket).unwrap(), Some(Status { launched: true }));
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn telemetry_in_another_module() {
+        run_launch_sequence().unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+use sfsm::*;
+use std::cell::RefCell;
+
+// This example requires the trace, trace-steps and trace-messages features to be enabled to run.
+// The sensor samples with a high rate, which would flood the log. The #[sfsm(trace = "...")]
+// attribute only compiles in the transitions and messages of the state machine, while the
+// #[sfsm(mute)] markers silence the sampling state, the transition back to idle and the polled
+// samples. The rare transition into the fault state is still traced.
+
+thread_local! {
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+    LOGS.with(|logs| logs.borrow_mut().push(log.to_string()));
+}
+
+fn logs() -> Vec<String> {
+    LOGS.with(|logs| logs.borrow().clone())
+}
+
+struct Idle {
+    samples: u8,
+}
+struct Sampling {
+    samples: u8,
+}
+struct Fault {}
+
+add_state_machine!(
+    #[sfsm(trace = "transitions, messages")]
+    Sensor,
+    Idle,
+    [Idle, #[sfsm(mute)] Sampling, Fault],
+    [
+        Idle => Sampling,
+        #[sfsm(mute)] Sampling => Idle,
+        Sampling => Fault,
+    ]
+);
+
+struct Sample {}
+#[derive(Debug)]
+struct Calibrate {}
+
+add_messages!(
+    Sensor,
+    [
+        #[sfsm(mute)] Sample <- Sampling,
+        Calibrate -> Idle,
+    ]
+);
+
+derive_state!(Idle);
+derive_state!(Fault);
+
+impl State for Sampling {
+    fn execute(&mut self) {
+        self.samples += 1;
+    }
+}
+
+impl From<Idle> for Sampling {
+    fn from(idle: Idle) -> Self {
+        Sampling { samples: idle.samples }
+    }
+}
+derive_transition!(Idle, Sampling, TransitGuard::Transit);
+
+impl From<Sampling> for Idle {
+    fn from(sampling: Sampling) -> Self {
+        Idle { samples: sampling.samples }
+    }
+}
+impl Transition<Idle> for Sampling {
+    fn guard(&self) -> TransitGuard {
+        (self.samples < 2).into()
+    }
+}
+
+derive_transition_into!(Sampling, Fault);
+impl Transition<Fault> for Sampling {
+    fn guard(&self) -> TransitGuard {
+        (self.samples >= 2).into()
+    }
+}
+
+impl ReturnMessage<Sample> for Sampling {
+    fn return_message(&mut self) -> Option<Sample> {
+        Some(Sample {})
+    }
+}
+
+impl ReceiveMessage<Calibrate> for Idle {
+    fn receive_message(&mut self, _message: Calibrate) {}
+}
+
+fn run_sensor() -> Result<(), SfsmError> {
+    let mut sensor = Sensor::new();
+    sensor.start(Idle { samples: 0 })?;
+    while !IsState::<Fault>::is_state(&sensor) {
+        if IsState::<Sampling>::is_state(&sensor) {
+            PollMessage::<Sampling, Sample>::poll_message(&mut sensor).unwrap();
+        } else {
+            PushMessage::<Idle, Calibrate>::push_message(&mut sensor, Calibrate {}).unwrap();
+        }
+        sensor.step()?;
+    }
+
+    assert_eq!(logs(), [
+        "Sensor: Start - Idle",
+        "Sensor: Push - Calibrate to Idle",
+        "Sensor: Exit - Idle",
+        "Sensor: Transit - From Idle to Sampling",
+        "Sensor: Enter - Idle",
+        "Sensor: Push - Calibrate to Idle",
+        "Sensor: Exit - Idle",
+        "Sensor: Transit - From Idle to Sampling",
+        "Sensor: Transit - From Sampling to Fault",
+        "Sensor: Enter - Fault",
+    ]);
+    Ok(())
+}
+
+fn main() {
+    run_sensor().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_sensor;
+
+    #[test]
+    fn sensor_with_muted_trace_points() {
+        run_sensor().unwrap();
+    }
+}
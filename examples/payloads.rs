@@ -0,0 +1,112 @@
+use sfsm::*;
+use std::cell::RefCell;
+use std::fmt;
+
+// This example requires the trace-payloads feature to be enabled to run.
+// Besides the name of the message, the pushed and polled messages themselves are traced. They are
+// formatted with their TraceFormat implementation if there is one and with Debug otherwise.
+// Messages that implement neither are traced as "..".
+
+thread_local! {
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+    LOGS.with(|logs| logs.borrow_mut().push(log.to_string()));
+}
+
+fn logs() -> Vec<String> {
+    LOGS.with(|logs| logs.borrow().clone())
+}
+
+struct WaitForLaunch {
+    countdown: u8,
+}
+
+// Traced with Debug
+#[derive(Debug)]
+struct StartLaunch {
+    countdown: u8,
+}
+
+// Traced with TraceFormat, which also works on no_std targets
+struct Abort {
+    reason: &'static str,
+}
+
+impl TraceFormat for Abort {
+    fn trace_format(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "abort because {}", self.reason)
+    }
+}
+
+// Can not be formatted at all
+struct Status {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch],
+    []
+);
+
+derive_state!(WaitForLaunch);
+
+add_messages!(
+    Rocket,
+    [
+        StartLaunch -> WaitForLaunch,
+        Abort -> WaitForLaunch,
+        Status <- WaitForLaunch,
+    ]
+);
+
+impl ReceiveMessage<StartLaunch> for WaitForLaunch {
+    fn receive_message(&mut self, message: StartLaunch) {
+        self.countdown = message.countdown;
+    }
+}
+
+impl ReceiveMessage<Abort> for WaitForLaunch {
+    fn receive_message(&mut self, _message: Abort) {
+        self.countdown = 0;
+    }
+}
+
+impl ReturnMessage<Status> for WaitForLaunch {
+    fn return_message(&mut self) -> Option<Status> {
+        Some(Status {})
+    }
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { countdown: 0 })?;
+
+    PushMessage::<WaitForLaunch, StartLaunch>::push_message(&mut rocket, StartLaunch { countdown: 10 }).unwrap();
+    // The error returns the message, which can not be unwrapped without Debug
+    assert!(PushMessage::<WaitForLaunch, Abort>::push_message(&mut rocket, Abort { reason: "wind" }).is_ok());
+    PollMessage::<WaitForLaunch, Status>::poll_message(&mut rocket).unwrap();
+
+    let logs = logs();
+    assert!(logs.contains(&"Rocket: Push - StartLaunch to WaitForLaunch: StartLaunch { countdown: 10 }".to_string()));
+    assert!(logs.contains(&"Rocket: Push - Abort to WaitForLaunch: abort because wind".to_string()));
+    assert!(logs.contains(&"Rocket: Poll - Status from WaitForLaunch: ..".to_string()));
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn launch_sequence_with_payloads() {
+        run_launch_sequence().unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+use sfsm::*;
+
+// The commands of the ground station arrive over the radio at any time, while the rocket only
+// steps once per control cycle. Instead of pushing them directly into the active state, the
+// commands are put into the message queue of the state machine and delivered at the start of the
+// next step. Commands that the active state does not receive are dropped. The queue is bounded,
+// so a full queue hands the command back.
+
+pub struct WaitForLaunch {
+    do_launch: bool,
+}
+pub struct Launch {
+    aborted: bool,
+}
+
+add_state_machine!(
+    #[sfsm(queue = 2)]
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+
+impl From<WaitForLaunch> for Launch {
+    fn from(_: WaitForLaunch) -> Self {
+        Launch { aborted: false }
+    }
+}
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        self.do_launch.into()
+    }
+}
+
+pub struct StartLaunch {}
+pub struct Abort {}
+pub struct Ping {}
+
+add_messages!(
+    #[sfsm(dispatch)]
+    Rocket,
+    [
+        StartLaunch -> WaitForLaunch,
+        Abort -> Launch,
+        Ping -> *,
+    ]
+);
+
+impl ReceiveMessage<StartLaunch> for WaitForLaunch {
+    fn receive_message(&mut self, _message: StartLaunch) {
+        self.do_launch = true;
+    }
+}
+
+impl ReceiveMessage<Abort> for Launch {
+    fn receive_message(&mut self, _message: Abort) {
+        self.aborted = true;
+    }
+}
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { do_launch: false })?;
+
+    assert!(EnqueueMessage::<RocketMessages>::enqueue_message(&mut rocket, StartLaunch {}).is_ok());
+    assert!(EnqueueMessage::<RocketMessages>::enqueue_message(&mut rocket, StartLaunch {}).is_ok());
+    let full = EnqueueMessage::<RocketMessages>::enqueue_message(&mut rocket, StartLaunch {});
+    assert!(matches!(full, Err(MessageError::QueueIsFull(RocketMessages::StartLaunch(_)))));
+
+    // None of the states receives Ping, so it could never be delivered and is not queued
+    let ping = EnqueueMessage::<RocketMessages>::enqueue_message(&mut rocket, Ping {});
+    assert!(matches!(ping, Err(MessageError::NotAccepted { message: RocketMessages::Ping(_), .. })));
+
+    // StartLaunch is delivered to WaitForLaunch before it is executed and the rocket lifts off
+    rocket.step()?;
+    assert!(IsState::<Launch>::is_state(&rocket));
+
+    // WaitForLaunch does not become active again, so the queued StartLaunch commands are dropped
+    // at the next step instead of filling up the queue for good
+    assert!(EnqueueMessage::<RocketMessages>::enqueue_message(&mut rocket, StartLaunch {}).is_ok());
+    assert!(EnqueueMessage::<RocketMessages>::enqueue_message(&mut rocket, StartLaunch {}).is_ok());
+    rocket.step()?;
+    assert!(EnqueueMessage::<RocketMessages>::dequeue_message(&mut rocket).is_none());
+
+    // So there is room for Abort again, which is delivered to Launch at the next step
+    assert!(EnqueueMessage::<RocketMessages>::enqueue_message(&mut rocket, Abort {}).is_ok());
+    rocket.step()?;
+    match rocket.stop()? {
+        RocketStates::LaunchState(Some(launch)) => assert!(launch.aborted),
+        _ => panic!("The rocket must have launched"),
+    }
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn queued_launch_sequence() {
+        run_launch_sequence().unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+use sfsm::*;
+
+// The controller asks the sensor for a reading. Pushing the request and polling the reading
+// afterwards does not guarantee that the polled reading answers that request. Instead, the
+// request is declared with Request => Reply @ State and the state answers it right away.
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+}
+
+pub struct Calibrating {}
+pub struct Measuring {
+    offset: i16,
+}
+
+add_state_machine!(
+    Sensor,
+    Calibrating,
+    [Calibrating, Measuring],
+    [
+        Calibrating => Measuring,
+    ]
+);
+
+derive_state!(Calibrating);
+derive_state!(Measuring);
+
+impl From<Calibrating> for Measuring {
+    fn from(_: Calibrating) -> Self {
+        Measuring { offset: -2 }
+    }
+}
+derive_transition!(Calibrating, Measuring, TransitGuard::Transit);
+
+#[derive(Debug)]
+pub struct ReadTemperature {
+    raw: i16,
+}
+#[derive(Debug, PartialEq)]
+pub struct Temperature {
+    celsius: i16,
+}
+
+add_messages!(
+    Sensor,
+    [
+        ReadTemperature => Temperature @ Measuring,
+    ]
+);
+
+impl ReplyMessage<ReadTemperature, Temperature> for Measuring {
+    fn reply_message(&mut self, request: ReadTemperature) -> Temperature {
+        Temperature { celsius: request.raw + self.offset }
+    }
+}
+
+fn run_measurement() -> Result<(), SfsmError> {
+    let mut sensor = Sensor::new();
+    sensor.start(Calibrating {})?;
+
+    // The sensor can not answer while it is calibrating, which is the state with the id 0
+    let rejected = RequestMessage::<Measuring, ReadTemperature, Temperature>::request_message(&mut sensor, ReadTemperature { raw: 20 });
+    assert!(matches!(rejected, Err(MessageError::OtherStateIsActive { message: ReadTemperature { raw: 20 }, active_state: 0, .. })));
+
+    sensor.step()?;
+    let temperature = RequestMessage::<Measuring, ReadTemperature, Temperature>::request_message(&mut sensor, ReadTemperature { raw: 20 });
+    assert_eq!(temperature.unwrap(), Temperature { celsius: 18 });
+    Ok(())
+}
+
+fn main() {
+    run_measurement().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_measurement;
+
+    #[test]
+    fn request_temperature() {
+        run_measurement().unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+use sfsm::*;
+
+// The sensor and the controller run as separate state machines. The readings of the sensor must
+// reach the controller, and the controller sets the sample rate of the sensor. Instead of polling
+// and pushing the messages by hand in the main loop, the routes are declared with add_routes! and
+// the generated pump function moves the messages once per cycle.
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+}
+
+pub struct Calibrating {}
+pub struct Measuring {
+    sample: u16,
+    rate: u8,
+}
+
+add_state_machine!(
+    Sensor,
+    Calibrating,
+    [Calibrating, Measuring],
+    [
+        Calibrating => Measuring,
+    ]
+);
+
+derive_state!(Calibrating);
+impl State for Measuring {
+    fn execute(&mut self) {
+        self.sample += 10;
+    }
+}
+
+impl From<Calibrating> for Measuring {
+    fn from(_: Calibrating) -> Self {
+        Measuring { sample: 0, rate: 1 }
+    }
+}
+derive_transition!(Calibrating, Measuring, TransitGuard::Transit);
+
+pub struct Regulating {
+    readings: Vec<u16>,
+}
+
+add_state_machine!(
+    Controller,
+    Regulating,
+    [Regulating],
+    []
+);
+
+derive_state!(Regulating);
+
+#[derive(Debug)]
+pub struct Reading {
+    value: u16,
+}
+#[derive(Debug)]
+pub struct SampleRate {
+    hertz: u8,
+}
+#[derive(Debug)]
+pub struct Configure {
+    rate: u8,
+}
+
+impl From<SampleRate> for Configure {
+    fn from(sample_rate: SampleRate) -> Self {
+        Configure { rate: sample_rate.hertz }
+    }
+}
+
+add_messages!(
+    Sensor,
+    [
+        Reading <- Measuring,
+        Configure -> Measuring,
+    ]
+);
+
+add_messages!(
+    Controller,
+    [
+        Reading -> Regulating,
+        SampleRate <- *,
+    ]
+);
+
+impl ReturnMessage<Reading> for Measuring {
+    fn return_message(&mut self) -> Option<Reading> {
+        Some(Reading { value: self.sample })
+    }
+}
+
+impl ReceiveMessage<Configure> for Measuring {
+    fn receive_message(&mut self, message: Configure) {
+        self.rate = message.rate;
+    }
+}
+
+impl ReceiveMessage<Reading> for Regulating {
+    fn receive_message(&mut self, message: Reading) {
+        self.readings.push(message.value);
+    }
+}
+
+impl ReturnMessage<SampleRate> for Regulating {
+    fn return_message(&mut self) -> Option<SampleRate> {
+        // Sample faster once the readings start to rise
+        match self.readings.last() {
+            Some(value) if *value > 0 => Some(SampleRate { hertz: 10 }),
+            _ => None,
+        }
+    }
+}
+
+add_routes!(
+    pump,
+    [
+        Sensor: Reading <- Measuring => Controller: Reading -> Regulating,
+        Controller: SampleRate <- * => Sensor: Configure -> Measuring,
+    ]
+);
+
+fn run_control_loop() -> Result<Vec<u16>, SfsmError> {
+    let mut sensor = Sensor::new();
+    sensor.start(Calibrating {})?;
+    let mut controller = Controller::new();
+    controller.start(Regulating { readings: vec![] })?;
+
+    // The sensor does not return readings while it is calibrating, so nothing is moved
+    pump(&mut sensor, &mut controller);
+
+    for _ in 0..3 {
+        sensor.step()?;
+        controller.step()?;
+        pump(&mut sensor, &mut controller);
+    }
+
+    // The reading is only polled if the target state is active, so it stays with the sensor
+    // until the controller has been started
+    let mut idle = Controller::new();
+    pump(&mut sensor, &mut idle);
+    idle.start(Regulating { readings: vec![] })?;
+    pump(&mut sensor, &mut idle);
+    match idle.stop()? {
+        ControllerStates::RegulatingState(Some(regulating)) => assert_eq!(regulating.readings, [20]),
+        _ => panic!("The controller must be regulating"),
+    }
+
+    match sensor.stop()? {
+        SensorStates::MeasuringState(Some(measuring)) => assert_eq!(measuring.rate, 10),
+        _ => panic!("The sensor must be measuring"),
+    }
+    match controller.stop()? {
+        ControllerStates::RegulatingState(Some(regulating)) => Ok(regulating.readings),
+        _ => panic!("The controller must be regulating"),
+    }
+}
+
+fn main() {
+    println!("{:?}", run_control_loop().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_control_loop;
+
+    #[test]
+    fn routed_readings() {
+        assert_eq!(run_control_loop().unwrap(), [0, 10, 20]);
+    }
+}
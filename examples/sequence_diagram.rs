@@ -0,0 +1,132 @@
+use sfsm::*;
+use sfsm_trace::SequenceDiagram;
+use std::sync::Mutex;
+
+// This example requires the trace-record feature to be enabled to run.
+// The records captured by the flight recorder are rendered as PlantUML sequence diagram with the
+// sfsm-trace crate. The diagram shows the transitions of both state machines and the messages
+// exchanged with them, which reads much better in an incident report than the plain logs.
+
+static RECORDER: Mutex<FlightRecorder<16>> = Mutex::new(FlightRecorder::new());
+
+#[sfsm_record]
+fn record(record: &TraceRecord) {
+    RECORDER.lock().unwrap().record(record);
+}
+
+pub struct WaitForLaunch {
+    do_launch: bool,
+}
+pub struct Launch {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        self.do_launch.into()
+    }
+}
+
+// The radio forwards the launch command to the rocket
+pub struct Listening {}
+
+add_state_machine!(
+    Radio,
+    Listening,
+    [Listening],
+    []
+);
+
+derive_state!(Listening);
+
+#[derive(Debug)]
+pub struct Command {}
+
+add_messages!(
+    Radio,
+    [
+        Command -> Listening,
+    ]
+);
+
+#[derive(Debug)]
+pub struct StartLaunch {}
+pub struct Status {}
+
+add_messages!(
+    Rocket,
+    [
+        StartLaunch -> WaitForLaunch,
+        Status <- Launch,
+    ]
+);
+
+impl ReceiveMessage<Command> for Listening {
+    fn receive_message(&mut self, _message: Command) {}
+}
+
+impl ReceiveMessage<StartLaunch> for WaitForLaunch {
+    fn receive_message(&mut self, _message: StartLaunch) {
+        self.do_launch = true;
+    }
+}
+
+impl ReturnMessage<Status> for Launch {
+    fn return_message(&mut self) -> Option<Status> {
+        Some(Status {})
+    }
+}
+
+fn run_launch_sequence() -> Result<String, SfsmError> {
+    let mut rocket = Rocket::new();
+    let mut radio = Radio::new();
+    radio.start(Listening {})?;
+    rocket.start(WaitForLaunch { do_launch: false })?;
+
+    PushMessage::<Listening, Command>::push_message(&mut radio, Command {}).unwrap();
+    PushMessage::<WaitForLaunch, StartLaunch>::push_message(&mut rocket, StartLaunch {}).unwrap();
+    rocket.step()?;
+    PollMessage::<Launch, Status>::poll_message(&mut rocket).unwrap();
+    rocket.stop()?;
+
+    let mut diagram = SequenceDiagram::new();
+    diagram.extend(RECORDER.lock().unwrap().iter());
+    Ok(diagram.plantuml())
+}
+
+fn main() {
+    print!("{}", run_launch_sequence().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn launch_sequence_as_diagram() {
+        assert_eq!(run_launch_sequence().unwrap(), "@startuml
+participant \"Caller\" as C
+participant \"Radio\" as M0
+participant \"Rocket\" as M1
+note over M0 : Start - Listening
+note over M1 : Start - WaitForLaunch
+C -> M0 : Command to Listening
+C -> M1 : StartLaunch to WaitForLaunch
+M1 -> M1 : WaitForLaunch to Launch
+M1 --> C : Status from Launch
+note over M1 : Stop - Launch
+@enduml
+");
+    }
+}
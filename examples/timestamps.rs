@@ -0,0 +1,91 @@
+use sfsm::*;
+use std::cell::{Cell, RefCell};
+
+// This example requires the trace and trace-time features to be enabled to run.
+// Every trace point is prefixed with the time of the clock registered with #[sfsm_clock] and the
+// number of steps the state machine has executed. This allows to correlate the traces with other
+// logs and to measure how long the state machine stayed in a state.
+
+thread_local! {
+    // A simulated clock in milliseconds. On a target, this would be a timer.
+    static TIME: Cell<u64> = const { Cell::new(0) };
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+#[sfsm_clock]
+fn clock() -> u64 {
+    TIME.with(|time| time.get())
+}
+
+fn advance(millis: u64) {
+    TIME.with(|time| time.set(time.get() + millis));
+}
+
+#[sfsm_trace]
+fn trace(log: &str) {
+    println!("{}", log);
+    LOGS.with(|logs| logs.borrow_mut().push(log.to_string()));
+}
+
+fn logs() -> Vec<String> {
+    LOGS.with(|logs| logs.borrow().clone())
+}
+
+struct WaitForLaunch {
+    countdown: u8,
+}
+struct Launch {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+impl State for WaitForLaunch {
+    fn execute(&mut self) {
+        self.countdown -= 1;
+    }
+}
+derive_state!(Launch);
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        (self.countdown == 0).into()
+    }
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { countdown: 3 })?;
+    while !IsState::<Launch>::is_state(&rocket) {
+        advance(100);
+        rocket.step()?;
+    }
+
+    assert_eq!(logs(), [
+        "[0 #0] Rocket: Start - WaitForLaunch",
+        "[300 #3] Rocket: Exit - WaitForLaunch",
+        "[300 #3] Rocket: Transit - From WaitForLaunch to Launch",
+        "[300 #3] Rocket: Enter - Launch",
+    ]);
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn launch_sequence_with_timestamps() {
+        run_launch_sequence().unwrap();
+    }
+}
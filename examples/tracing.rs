@@ -1,5 +1,6 @@
 use sfsm::*;
 use sfsm::message::{MessageError, ReturnMessage, ReceiveMessage};
+use std::cell::Cell;
 
 /// This example requires the trace* features to be enabled to run
 
@@ -8,9 +9,19 @@ use sfsm::message::{MessageError, ReturnMessage, ReceiveMessage};
 /// to what ever logging mechanism desired.
 #[sfsm_trace]
 fn trace(log: &str) {
+    TRACES.with(|traces| traces.set(traces.get() + 1));
     println!("{}", log);
 }
 
+thread_local! {
+    // Count the logs to check which trace points are enabled
+    static TRACES: Cell<usize> = const { Cell::new(0) };
+}
+
+fn traces() -> usize {
+    TRACES.with(|traces| traces.get())
+}
+
 struct WaitForLaunch {
     malfunction: bool,
     do_launch: bool,
@@ -121,16 +132,42 @@ fn run_launch_sequence_with_message() -> Result<(), SfsmError> {
     Ok(())
 }
 
+fn run_launch_sequence_with_trace_mask() -> Result<(), SfsmError> {
+
+    let mut rocket = Rocket::new();
+
+    // Disable all trace points of this instance
+    rocket.set_trace_mask(TraceMask::NONE);
+    let traces_before = traces();
+    rocket.start(WaitForLaunch { malfunction: false, do_launch: false })?;
+    rocket.step()?;
+    assert_eq!(traces(), traces_before);
+
+    // And enable only the transitions again
+    rocket.set_trace_mask(TraceMask::TRANSITIONS);
+    PushMessage::<WaitForLaunch, StartLaunch>::push_message(&mut rocket, StartLaunch {start: true}).unwrap();
+    assert_eq!(traces(), traces_before);
+    rocket.step()?;
+    assert!(traces() > traces_before);
+    Ok(())
+}
+
 fn main() {
     run_launch_sequence_with_message().unwrap();
+    run_launch_sequence_with_trace_mask().unwrap();
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::run_launch_sequence_with_message;
+    use crate::{run_launch_sequence_with_message, run_launch_sequence_with_trace_mask};
 
     #[test]
     fn launch_sequence_with_message() {
         run_launch_sequence_with_message().unwrap();
     }
+
+    #[test]
+    fn launch_sequence_with_trace_mask() {
+        run_launch_sequence_with_trace_mask().unwrap();
+    }
 }
@@ -0,0 +1,119 @@
+use sfsm::*;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+// This example requires the trace-tracing feature to be enabled to run.
+// Every state machine enters a span named after itself while it is being started, stepped,
+// stopped or accessed with messages. The events carry the machine and the states as fields.
+
+/// A subscriber that stores the events together with the span they occurred in. Usually, a
+/// subscriber such as tracing_subscriber::fmt would be used instead.
+#[derive(Clone, Default)]
+struct Collector {
+    spans: Arc<Mutex<Vec<String>>>,
+    stack: Arc<Mutex<Vec<u64>>>,
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+/// Renders all fields of an event as ``` name=value ```
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.push_str(&format!(" {}={:?}", field.name(), value));
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(span.metadata().name().to_string());
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let spans = self.spans.lock().unwrap();
+        let span = self.stack.lock().unwrap().last().map(|id| spans[*id as usize - 1].clone());
+        let mut fields = Fields(String::new());
+        event.record(&mut fields);
+        let event = format!("{}:{}", span.unwrap_or_default(), fields.0);
+        println!("{}", event);
+        self.events.lock().unwrap().push(event);
+    }
+
+    fn enter(&self, span: &Id) {
+        self.stack.lock().unwrap().push(span.into_u64());
+    }
+
+    fn exit(&self, _span: &Id) {
+        self.stack.lock().unwrap().pop();
+    }
+}
+
+struct WaitForLaunch {
+    do_launch: bool,
+}
+struct Launch {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        self.do_launch.into()
+    }
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let collector = Collector::default();
+
+    tracing::subscriber::with_default(collector.clone(), || {
+        let mut rocket = Rocket::new();
+        rocket.start(WaitForLaunch { do_launch: true })?;
+        rocket.step()?;
+        assert!(IsState::<Launch>::is_state(&rocket));
+        rocket.stop()?;
+        Ok::<(), SfsmError>(())
+    })?;
+
+    let events = collector.events.lock().unwrap();
+    assert!(events.contains(&"Rocket: message=Start machine=\"Rocket\" state=\"WaitForLaunch\"".to_string()));
+    assert!(events.contains(&"Rocket: message=Transit machine=\"Rocket\" from=\"WaitForLaunch\" to=\"Launch\"".to_string()));
+    assert!(events.contains(&"Rocket: message=Stop machine=\"Rocket\"".to_string()));
+    Ok(())
+}
+
+fn main() {
+    run_launch_sequence().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run_launch_sequence;
+
+    #[test]
+    fn launch_sequence_with_tracing() {
+        run_launch_sequence().unwrap();
+    }
+}
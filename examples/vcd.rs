@@ -0,0 +1,158 @@
+use sfsm::*;
+use sfsm_trace::VcdWriter;
+use std::cell::{Cell, RefCell};
+
+// This example requires the trace-record and trace-time features to be enabled to run.
+// The records of the state machines are written as Value Change Dump with the VcdWriter of the
+// sfsm-trace crate. Every state machine becomes a signal whose value is the id of its active
+// state, so the dump can be opened in GTKWave next to the captures of a logic analyser. Instead
+// of a vector, the writer would usually write into a file. Multiple instances of a state machine
+// become separate signals once they have been given distinct instance ids.
+
+thread_local! {
+    // A simulated clock in milliseconds. On a target, this would be a timer.
+    static TIME: Cell<u64> = const { Cell::new(0) };
+    static VCD: RefCell<VcdWriter<Vec<u8>>> = RefCell::new(VcdWriter::new(Vec::new(), "1 ms"));
+}
+
+#[sfsm_clock]
+fn clock() -> u64 {
+    TIME.with(|time| time.get())
+}
+
+fn advance(millis: u64) {
+    TIME.with(|time| time.set(time.get() + millis));
+}
+
+#[sfsm_record]
+fn record(record: &TraceRecord) {
+    VCD.with(|vcd| vcd.borrow_mut().record(record).unwrap());
+}
+
+pub struct WaitForLaunch {
+    countdown: u8,
+}
+pub struct Launch {}
+pub struct HandleMalfunction {}
+
+#[derive(Debug)]
+pub enum RocketMalfunction {
+    BoostersFellOff,
+}
+
+add_fallible_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch, HandleMalfunction],
+    [
+        WaitForLaunch => Launch,
+        HandleMalfunction => WaitForLaunch
+    ],
+    RocketMalfunction,
+    HandleMalfunction
+);
+
+impl TryState for WaitForLaunch {
+    type Error = RocketMalfunction;
+    fn try_execute(&mut self) -> Result<(), Self::Error> {
+        self.countdown -= 1;
+        Ok(())
+    }
+}
+
+impl TryState for Launch {
+    type Error = RocketMalfunction;
+    fn try_execute(&mut self) -> Result<(), Self::Error> {
+        Err(RocketMalfunction::BoostersFellOff)
+    }
+}
+
+impl TryState for HandleMalfunction {
+    type Error = RocketMalfunction;
+}
+
+impl TryErrorState for HandleMalfunction {
+    fn consume_error(&mut self, _err: Self::Error) {}
+}
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl TryTransition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        (self.countdown == 0).into()
+    }
+}
+
+impl From<HandleMalfunction> for WaitForLaunch {
+    fn from(_: HandleMalfunction) -> Self {
+        WaitForLaunch { countdown: 2 }
+    }
+}
+derive_try_transition!(HandleMalfunction, WaitForLaunch, TransitGuard::Transit);
+
+derive_transition_into!(WaitForLaunch, HandleMalfunction);
+derive_transition_into!(Launch, HandleMalfunction);
+
+fn run_launch_sequence() -> Result<String, ExtendedSfsmError<RocketMalfunction>> {
+    VCD.with(|vcd| vcd.borrow_mut().add_machine(Rocket::TRACE_INFO).unwrap());
+
+    let mut rocket = Rocket::new();
+    rocket.start(WaitForLaunch { countdown: 2 })?;
+    for _ in 0..4 {
+        advance(100);
+        rocket.step()?;
+    }
+    advance(100);
+    rocket.stop()?;
+
+    let dump = VCD.with(|vcd| String::from_utf8(vcd.borrow().get_ref().clone()).unwrap());
+    Ok(dump)
+}
+
+fn main() {
+    print!("{}", run_launch_sequence().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn run_two_rockets() -> Result<String, ExtendedSfsmError<RocketMalfunction>> {
+        VCD.with(|vcd| {
+            let mut vcd = vcd.borrow_mut();
+            vcd.add_instance(Rocket::TRACE_INFO, 1).unwrap();
+            vcd.add_instance(Rocket::TRACE_INFO, 2).unwrap();
+        });
+
+        let mut left = Rocket::new();
+        left.set_trace_instance(1);
+        let mut right = Rocket::new();
+        right.set_trace_instance(2);
+
+        left.start(WaitForLaunch { countdown: 1 })?;
+        right.start(WaitForLaunch { countdown: 2 })?;
+        advance(100);
+        left.step()?;
+        right.step()?;
+
+        let dump = VCD.with(|vcd| String::from_utf8(vcd.borrow().get_ref().clone()).unwrap());
+        Ok(dump)
+    }
+
+    #[test]
+    fn launch_sequence_as_vcd() {
+        let dump = run_launch_sequence().unwrap();
+        assert!(dump.contains("$comment Rocket: 0 = WaitForLaunch, 1 = Launch, 2 = HandleMalfunction $end"));
+        assert!(dump.contains("$var wire 8 ! Rocket $end"));
+        // The active states change at the times of the clock
+        assert!(dump.ends_with("$end\nb0 !\n#200\nb1 !\n#300\nb10 !\n#400\nb0 !\n#500\nbx !\n"));
+    }
+
+    #[test]
+    fn instances_as_separate_signals() {
+        let dump = run_two_rockets().unwrap();
+        assert!(dump.contains("$var wire 8 ! Rocket_1 $end"));
+        assert!(dump.contains("$var wire 8 \" Rocket_2 $end"));
+        // Only the left rocket launches after the first step
+        assert!(dump.ends_with("$end\nb0 !\nb0 \"\n#100\nb1 !\n"));
+    }
+}
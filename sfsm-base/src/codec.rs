@@ -0,0 +1,111 @@
+use crate::message::MessageError;
+use crate::message::__protected::DispatchMessage;
+
+/// Error type that will be returned if a frame could not be decoded or a message could not be
+/// encoded into a frame.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum CodecError {
+    /// The frame is empty and does not even contain the tag.
+    EmptyFrame,
+    /// The tag of the frame does not belong to a message that is pushed into the state machine.
+    UnknownTag(u8),
+    /// The message can not be encoded, as it is not polled from the state machine.
+    NotEncodable,
+    /// The payload of the frame could not be deserialized or the message could not be serialized
+    /// into the buffer.
+    Payload(postcard::Error),
+}
+
+impl From<postcard::Error> for CodecError {
+    fn from(err: postcard::Error) -> Self {
+        CodecError::Payload(err)
+    }
+}
+
+/// Error type that will be returned if a frame is pushed into the state machine.
+#[derive(Debug)]
+pub enum FrameError<T> {
+    /// The frame could not be decoded. Nothing has been pushed.
+    Codec(CodecError),
+    /// The frame was decoded, but the message could not be pushed.
+    Message(MessageError<T>),
+}
+
+/// The MessageCodec trait implementation will be generated by the add_messages! macro for the enum
+/// of all declared messages if they are declared with ``` #[sfsm(codec)] ```. A frame consists of
+/// the tag of the message, followed by the message serialized with postcard. The tags are given in
+/// the order the messages were declared, unless they are pinned with ``` #[sfsm(tag = N)] ```.
+///
+/// Only the messages that are pushed can be decoded and only the messages that are polled can be
+/// encoded. The pushed messages must therefore implement ``` Deserialize ``` and the polled
+/// messages ``` Serialize ```.
+pub trait MessageCodec: Sized {
+    /// Decodes the message of a frame
+    fn decode(frame: &[u8]) -> Result<Self, CodecError>;
+
+    /// Encodes the message into the buffer and returns the part of the buffer that holds the frame
+    fn encode<'b>(&self, buffer: &'b mut [u8]) -> Result<&'b mut [u8], CodecError>;
+}
+
+/// Decodes frames and pushes their messages into the active state and encodes the messages polled
+/// from the active state. It is implemented for every state machine whose message enum implements
+/// ``` MessageCodec ```.
+/// ```rust,ignore
+/// rocket.push_frame(&[0, 1])?;
+/// let mut buffer = [0u8; 16];
+/// if let Some(frame) = rocket.poll_frame(&mut buffer)? {
+///     // Send the frame
+/// }
+/// ```
+pub trait DispatchFrame<Messages: MessageCodec>: DispatchMessage<Messages> {
+    /// Decodes the frame and pushes the message into the active state
+    fn push_frame(&mut self, frame: &[u8]) -> Result<(), FrameError<Messages>> {
+        let message = Messages::decode(frame).map_err(FrameError::Codec)?;
+        self.push(message).map_err(FrameError::Message)
+    }
+
+    /// Polls a message from the active state and encodes it into the buffer. Returns the part of
+    /// the buffer that holds the frame, or None if the active state has no message to return.
+    fn poll_frame<'b>(&mut self, buffer: &'b mut [u8]) -> Result<Option<&'b mut [u8]>, CodecError> {
+        match self.poll_any() {
+            Some(message) => message.encode(buffer).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T, Messages> DispatchFrame<Messages> for T
+    where T: DispatchMessage<Messages>, Messages: MessageCodec {}
+
+pub mod __protected {
+    use super::CodecError;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    /// Splits the tag off the frame.
+    pub fn decode_frame_tag(frame: &[u8]) -> Result<(u8, &[u8]), CodecError> {
+        match frame.split_first() {
+            Some((tag, payload)) => Ok((*tag, payload)),
+            None => Err(CodecError::EmptyFrame),
+        }
+    }
+
+    /// Deserializes the payload of a frame.
+    pub fn decode_frame_payload<T: DeserializeOwned>(payload: &[u8]) -> Result<T, CodecError> {
+        Ok(postcard::from_bytes(payload)?)
+    }
+
+    /// Writes the tag and the serialized message into the buffer and returns the part of the
+    /// buffer that holds the frame.
+    pub fn encode_frame<'b, T: Serialize>(tag: u8, message: &T, buffer: &'b mut [u8]) -> Result<&'b mut [u8], CodecError> {
+        let len = match buffer.split_first_mut() {
+            Some((first, payload)) => {
+                *first = tag;
+                postcard::to_slice(message, payload)?.len()
+            }
+            None => return Err(CodecError::Payload(postcard::Error::SerializeBufferFull)),
+        };
+        Ok(&mut buffer[..len + 1])
+    }
+}
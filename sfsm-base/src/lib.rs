@@ -10,6 +10,13 @@ pub mod non_fallible;
 /// Contains definitions and code for the messaging system
 pub mod message;
 
+/// Contains definitions used to control the tracing of the state machines
+pub mod trace;
+
+/// Contains definitions to decode messages from frames and to encode them into frames
+#[cfg(feature = "codec")]
+pub mod codec;
+
 /// Enum used to indicate to the guard function if the transition should transit to the
 /// next state or remain in the current one.
 /// ```rust
@@ -35,7 +42,7 @@ pub mod message;
 ///     }
 /// # }
 /// ```
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum TransitGuard {
     /// Remains in the current state
     Remain,
@@ -106,6 +113,23 @@ pub mod __protected {
         fn peek_state(&self) -> &Self::StatesEnum;
     }
 
+    /// Trait that will be implemented for every state machine, fallible or not. It is kept apart
+    /// from StateMachine, so implementing StateMachine does not require it.
+    pub trait Transit: StateMachine {
+        /// Checks the transitions of the active state without executing it. This allows to react
+        /// to a pushed message right away instead of waiting for the next step.
+        fn transit(&mut self) -> Result<(), Self::Error>;
+    }
+
+    /// An implementation of this trait will be generated for every state of a fallible state
+    /// machine. It is used when a state fails to receive a message, to transit into the error
+    /// state like a failed ``` try_execute ``` does.
+    pub trait TransitToErrorState<FailedState: crate::TryState>: StateMachine {
+        /// Converts the active state into the error state and passes the error to its
+        /// ``` consume_error ``` function. If the error state itself failed, the error is returned.
+        fn transit_to_error_state(&mut self, err: FailedState::Error) -> Result<(), Self::Error>;
+    }
+
     /// An implementation of this trait will be generated for every state.
     /// This is can be used to test if the state machine is in a desired state.
     pub trait IsState<State>: StateMachine {
@@ -124,5 +148,11 @@ pub use non_fallible::*;
 pub use fallible::*;
 pub use message::*;
 pub use message::__protected::*;
+pub use trace::*;
+pub use trace::__protected::*;
+#[cfg(feature = "codec")]
+pub use codec::*;
+#[cfg(feature = "codec")]
+pub use codec::__protected::*;
 
 
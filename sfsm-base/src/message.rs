@@ -1,3 +1,5 @@
+use crate::fallible::TryState;
+
 
 
 /// Error type that will be returned if an error during the message polling or pushing occurred.
@@ -6,14 +8,176 @@
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum MessageError<T> {
-    /// Will be returned if the state is not active. If it originated during a push, the rejected messaged will be returned with the error.
-    StateIsNotActive(T),
+    /// Will be returned if no state is active. This is the case before the state machine has been started, or if a step was aborted by an internal error before the next state was set. If it originated during a push, the rejected message will be returned with the error.
+    NotStarted(T),
+    /// Will be returned if the message queue of the state machine is full. The rejected message will be returned with the error.
+    QueueIsFull(T),
+    /// Will be returned if another state is active. If it originated during a push, the rejected message will be returned with the error together with the id of the active state.
+    #[non_exhaustive]
+    OtherStateIsActive {
+        message: T,
+        active_state: u8,
+    },
+    /// Will be returned if a message broadcast to all states is pushed into or polled from an active state that does not receive or return it. The id of the active state is returned with the error.
+    #[non_exhaustive]
+    NotAccepted {
+        message: T,
+        active_state: u8,
+    },
+}
+
+impl<T> MessageError<T> {
+    /// Returns the error for a message whose state is not active, depending on which state is
+    /// active instead.
+    /// ```rust
+    /// # use sfsm_base::message::MessageError;
+    /// assert!(matches!(MessageError::inactive(2u8, Some(1)), MessageError::OtherStateIsActive { message: 2, active_state: 1, .. }));
+    /// assert!(matches!(MessageError::inactive(2u8, None), MessageError::NotStarted(2)));
+    /// ```
+    pub fn inactive(message: T, active_state: Option<u8>) -> Self {
+        match active_state {
+            Some(active_state) => MessageError::OtherStateIsActive { message, active_state },
+            None => MessageError::NotStarted(message),
+        }
+    }
+
+    /// Returns the error for a message that the active state does not receive or return.
+    /// ```rust
+    /// # use sfsm_base::message::MessageError;
+    /// assert!(matches!(MessageError::not_accepted(2u8, 1), MessageError::NotAccepted { message: 2, active_state: 1, .. }));
+    /// ```
+    pub fn not_accepted(message: T, active_state: u8) -> Self {
+        MessageError::NotAccepted { message, active_state }
+    }
+
+    /// Returns the rejected message
+    pub fn into_message(self) -> T {
+        match self {
+            MessageError::NotStarted(message)
+            | MessageError::QueueIsFull(message)
+            | MessageError::OtherStateIsActive { message, .. }
+            | MessageError::NotAccepted { message, .. } => message,
+        }
+    }
+
+    /// Returns the id of the active state if the message was rejected because of it
+    pub fn active_state(&self) -> Option<u8> {
+        match self {
+            MessageError::OtherStateIsActive { active_state, .. }
+            | MessageError::NotAccepted { active_state, .. } => Some(*active_state),
+            _ => None,
+        }
+    }
+
+    /// Converts the returned message, for example to wrap it into the generated message enum.
+    /// ```rust
+    /// # use sfsm_base::message::MessageError;
+    /// let err = MessageError::inactive(2u8, Some(1));
+    /// assert!(matches!(err.map(u32::from), MessageError::OtherStateIsActive { message: 2u32, active_state: 1, .. }));
+    /// ```
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> MessageError<U> {
+        match self {
+            MessageError::NotStarted(message) => MessageError::NotStarted(f(message)),
+            MessageError::QueueIsFull(message) => MessageError::QueueIsFull(f(message)),
+            MessageError::OtherStateIsActive { message, active_state } => MessageError::OtherStateIsActive { message: f(message), active_state },
+            MessageError::NotAccepted { message, active_state } => MessageError::NotAccepted { message: f(message), active_state },
+        }
+    }
+}
+
+/// Error type that will be returned if a message is pushed and the transitions are checked right
+/// after.
+#[derive(Debug)]
+pub enum PushTransitError<T, E> {
+    /// The message could not be pushed. The transitions have not been checked.
+    Message(MessageError<T>),
+    /// The message was pushed, but transiting failed with the error of the state machine.
+    Transit(E),
+}
+
+/// A queue with a fixed capacity that holds the messages of a state machine until they are
+/// delivered at the start of the next step. It does not allocate and can be used on its own as
+/// well.
+/// ```rust
+/// # use sfsm_base::message::MessageQueue;
+/// let mut queue = MessageQueue::<u8, 2>::new();
+/// assert!(queue.push(1).is_ok());
+/// assert!(queue.push(2).is_ok());
+/// assert_eq!(queue.push(3), Err(3));
+/// assert_eq!(queue.pop(), Some(1));
+/// assert_eq!(queue.len(), 1);
+/// ```
+pub struct MessageQueue<T, const N: usize> {
+    messages: [Option<T>; N],
+    next: usize,
+    len: usize,
+}
+
+impl<T, const N: usize> MessageQueue<T, N> {
+    const EMPTY: Option<T> = None;
+
+    /// Creates an empty queue
+    pub const fn new() -> Self {
+        Self {
+            messages: [Self::EMPTY; N],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /// Appends the message to the end of the queue. If the queue is full, the message is returned.
+    pub fn push(&mut self, message: T) -> Result<(), T> {
+        if self.len == N {
+            return Err(message);
+        }
+        self.messages[(self.next + self.len) % N] = Some(message);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Takes the oldest message out of the queue
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let message = self.messages[self.next].take();
+        self.next = (self.next + 1) % N;
+        self.len -= 1;
+        message
+    }
+
+    /// Returns the number of queued messages
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if no messages are queued
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if no more messages fit into the queue
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Returns the number of messages the queue can hold
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<T, const N: usize> Default for MessageQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub mod __protected {
-    use crate::message::MessageError;
-    use crate::__protected::StateMachine;
-    use crate::{ReturnMessage, ReceiveMessage, State};
+    use crate::message::{MessageError, PushTransitError};
+    use crate::__protected::{StateMachine, IsState, Transit};
+    use crate::{ReturnMessage, ReceiveMessage, ReplyMessage, TryReceiveMessage, State};
+    use core::marker::PhantomData;
 
     /// The PushMessage trait implementation will be generated by the add_message! macro and is used
     /// to send messages into the state machine where they will then be forwarded to the correct
@@ -31,6 +195,67 @@ pub mod __protected {
         fn push_message(&mut self, message: Message) -> Result<(), MessageError<Message>>;
     }
 
+    /// The TryPushMessage trait implementation will be generated by the add_message! macro for
+    /// messages marked with #[sfsm(fallible)]. If the state fails to receive the message, the state
+    /// machine transits into the error state.
+    pub trait TryPushMessage<TargetState, Message>: StateMachine
+        where TargetState: TryReceiveMessage<Message>
+    {
+        /// This will call the try_receive_message function of ``` FooState ```. If it returns an
+        /// error, the state is converted into the error state, which consumes the error.
+        ///```rust,ignore
+        /// use sfsm_base::TryPushMessage;
+        /// TryPushMessage::<FooState, FooMessage>::try_push_message(&mut sfsm, some_message)?;
+        ///```
+        fn try_push_message(&mut self, message: Message) -> Result<(), PushTransitError<Message, Self::Error>>;
+    }
+
+    /// Pushes a message and checks the transitions of the state right after, so the state can
+    /// transit in reaction to the message without waiting for the next step. It is implemented for
+    /// every state machine that implements the according PushMessage trait.
+    pub trait PushMessageAndTransit<TargetState, Message>: PushMessage<TargetState, Message> + Transit
+        where TargetState: State + ReceiveMessage<Message>
+    {
+        /// Pushes the message into ``` FooState ``` and checks its transitions. The state is not
+        /// executed.
+        ///```rust,ignore
+        /// use sfsm_base::PushMessageAndTransit;
+        /// PushMessageAndTransit::<FooState, FooMessage>::push_message_and_transit(&mut sfsm, some_message)?;
+        ///```
+        fn push_message_and_transit(&mut self, message: Message) -> Result<(), PushTransitError<Message, Self::Error>> {
+            PushMessage::<TargetState, Message>::push_message(self, message).map_err(PushTransitError::Message)?;
+            self.transit().map_err(PushTransitError::Transit)
+        }
+    }
+
+    impl<T, TargetState, Message> PushMessageAndTransit<TargetState, Message> for T
+        where T: PushMessage<TargetState, Message> + Transit,
+              TargetState: State + ReceiveMessage<Message>
+    {}
+
+    /// The fallible counterpart of PushMessageAndTransit. It is implemented for every state machine
+    /// that implements the according TryPushMessage trait.
+    pub trait TryPushMessageAndTransit<TargetState, Message>: TryPushMessage<TargetState, Message> + Transit
+        where TargetState: TryReceiveMessage<Message>
+    {
+        /// Tries to push the message into ``` FooState ``` and checks the transitions of the active
+        /// state. If the state failed to receive the message, these are the transitions of the error
+        /// state.
+        ///```rust,ignore
+        /// use sfsm_base::TryPushMessageAndTransit;
+        /// TryPushMessageAndTransit::<FooState, FooMessage>::try_push_message_and_transit(&mut sfsm, some_message)?;
+        ///```
+        fn try_push_message_and_transit(&mut self, message: Message) -> Result<(), PushTransitError<Message, Self::Error>> {
+            TryPushMessage::<TargetState, Message>::try_push_message(self, message)?;
+            self.transit().map_err(PushTransitError::Transit)
+        }
+    }
+
+    impl<T, TargetState, Message> TryPushMessageAndTransit<TargetState, Message> for T
+        where T: TryPushMessage<TargetState, Message> + Transit,
+              TargetState: TryReceiveMessage<Message>
+    {}
+
     /// The PollMessage trait implementation will be generated by the add_message! macro and is used
     /// to return messages from states.
     pub trait PollMessage<TargetState, Message>: StateMachine
@@ -44,6 +269,250 @@ pub mod __protected {
         ///```
         fn poll_message(&mut self) -> Result<Option<Message>, MessageError<()>>;
     }
+
+    /// The RequestMessage trait implementation will be generated by the add_messages! macro for
+    /// requests defined like ``` Query => Reply @ State ```. The request is pushed into the state
+    /// and its reply is returned right away.
+    pub trait RequestMessage<TargetState, Request, Reply>: StateMachine
+        where TargetState: State + ReplyMessage<Request, Reply>
+    {
+        /// This will call the reply_message function of ``` FooState ``` if it is active and return
+        /// its reply. If the state is not active, the request is returned with the error.
+        ///```rust,ignore
+        /// use sfsm_base::RequestMessage;
+        /// let reply = RequestMessage::<FooState, FooRequest, FooReply>::request_message(&mut sfsm, FooRequest {})?;
+        ///```
+        fn request_message(&mut self, request: Request) -> Result<Reply, MessageError<Request>>;
+    }
+
+    /// The DeliverMessage trait implementation will be generated by the add_messages! macro for the
+    /// enum of all messages that can be pushed into the state machine. It forwards the message to
+    /// the active state if that state has been declared to receive it.
+    pub trait DeliverMessage<Messages>: StateMachine {
+        /// Pushes the message into the active state. If the active state does not receive the
+        /// message, the message is returned with the error.
+        fn deliver_message(&mut self, message: Messages) -> Result<(), MessageError<Messages>>;
+
+        /// Checks if any state receives the message. Messages that are only polled, and messages
+        /// broadcast to all states that none of the states receives, can never be delivered.
+        fn receives(message: &Messages) -> bool;
+    }
+
+    /// The DispatchMessage trait implementation will be generated by the add_messages! macro for the
+    /// enum of all declared messages. It is a single entry point to push and poll messages, which
+    /// dispatches them by the active state. This helps to route messages that come from a queue or
+    /// a deserializer.
+    pub trait DispatchMessage<Messages>: StateMachine {
+        /// Pushes the message into the active state. If the active state does not receive the
+        /// message, the message is returned with the error.
+        ///```rust,ignore
+        /// use sfsm_base::DispatchMessage;
+        /// sfsm.push(RocketMessages::StartLaunch(StartLaunch {}))?;
+        ///```
+        fn push(&mut self, message: Messages) -> Result<(), MessageError<Messages>>;
+
+        /// Polls the messages the active state returns in the order they were declared and returns
+        /// the first one that is available.
+        ///```rust,ignore
+        /// use sfsm_base::DispatchMessage;
+        /// if let Some(RocketMessages::Status(status)) = sfsm.poll_any() {
+        ///     // Handle the status
+        /// }
+        ///```
+        fn poll_any(&mut self) -> Option<Messages>;
+    }
+
+    /// The EnqueueMessage trait implementation will be generated by the add_state_machine! macro if
+    /// the state machine has a message queue. The queued messages are delivered to the active
+    /// state at the start of the next step. Messages that the active state does not receive are
+    /// dropped, so the queue is empty after each step. Messages that have to wait for their state
+    /// are marked with #[sfsm(defer)] instead.
+    ///
+    /// Enqueuing requires a mutable reference to the state machine, just like stepping it. To
+    /// enqueue from an interrupt or another task, the state machine must be shared behind a
+    /// critical section or a mutex, or the messages are collected in a separate single producer
+    /// queue and enqueued by the task that steps the state machine.
+    pub trait EnqueueMessage<Messages>: StateMachine {
+        /// Appends the message to the queue of the state machine. If the queue is full, the message
+        /// is returned with the ``` QueueIsFull ``` error. A message that is only polled and can
+        /// therefore never be delivered is returned with the same error as a push.
+        ///```rust,ignore
+        /// use sfsm_base::EnqueueMessage;
+        /// EnqueueMessage::<RocketMessages>::enqueue_message(&mut sfsm, StartLaunch {})?;
+        ///```
+        fn enqueue_message<M: Into<Messages>>(&mut self, message: M) -> Result<(), MessageError<Messages>>;
+
+        /// Removes the oldest message from the queue, for example to handle the messages that the
+        /// active state does not receive before the next step drops them.
+        ///```rust,ignore
+        /// use sfsm_base::EnqueueMessage;
+        /// while let Some(message) = EnqueueMessage::<RocketMessages>::dequeue_message(&mut sfsm) {
+        ///     // Handle the message instead of delivering it
+        /// }
+        ///```
+        fn dequeue_message(&mut self) -> Option<Messages>;
+    }
+
+    /// The DeferMessage trait implementation will be generated by the add_state_machine! macro if
+    /// the state machine can hold deferred messages. It is used by the PushMessage implementation of
+    /// messages marked with #[sfsm(defer)] when their target state is not active. The deferred
+    /// messages are delivered right after the entry of their target state.
+    pub trait DeferMessage<Messages>: StateMachine {
+        /// Holds the message until the state ``` S ``` becomes active. If no more messages can be
+        /// held, the message is returned.
+        ///```rust,ignore
+        /// use sfsm_base::DeferMessage;
+        /// DeferMessage::<RocketMessages>::defer_message::<Launch, _>(&mut sfsm, Abort {})?;
+        ///```
+        fn defer_message<S, M: Into<Messages>>(&mut self, message: M) -> Result<(), M>
+            where Self: IsState<S>;
+    }
+
+    /// The PushAnyMessage trait implementation will be generated by the add_messages! macro for
+    /// messages that are broadcast to a list of states or to all states with ``` Message -> * ```.
+    pub trait PushAnyMessage<Message>: StateMachine {
+        /// Pushes the message into the active state if it receives the message. Otherwise, the
+        /// message is returned with the error.
+        ///```rust,ignore
+        /// use sfsm_base::PushAnyMessage;
+        /// PushAnyMessage::<Shutdown>::push_any_message(&mut sfsm, Shutdown {})?;
+        ///```
+        fn push_any_message(&mut self, message: Message) -> Result<(), MessageError<Message>>;
+
+        /// Returns whether the active state receives the message, without pushing it.
+        ///```rust,ignore
+        /// use sfsm_base::PushAnyMessage;
+        /// if PushAnyMessage::<Shutdown>::receives_any_message(&mut sfsm) {
+        ///     // Poll the message from the source
+        /// }
+        ///```
+        fn receives_any_message(&mut self) -> bool;
+    }
+
+    /// The PollAnyMessage trait implementation will be generated by the add_messages! macro for
+    /// messages that are polled from a list of states or from all states with ``` Message <- * ```.
+    pub trait PollAnyMessage<Message>: StateMachine {
+        /// Polls the message from the active state if it returns the message.
+        ///```rust,ignore
+        /// use sfsm_base::PollAnyMessage;
+        /// let status = PollAnyMessage::<Status>::poll_any_message(&mut sfsm)?;
+        ///```
+        fn poll_any_message(&mut self) -> Result<Option<Message>, MessageError<()>>;
+    }
+
+    /// Wraps a state to find out whether it receives or returns a message, without requiring it
+    /// to do so. The messages broadcast to all states use it, as the states that do not implement
+    /// the message traits have to be skipped. The methods are resolved with autoref
+    /// specialization: ``` MaybeMessage::<_, M>::new(state).receives() ``` takes the
+    /// implementation for ``` MaybeMessage ``` if the state receives the message and otherwise
+    /// the fallback for ``` &MaybeMessage ```.
+    pub struct MaybeMessage<'a, S, M> {
+        state: &'a mut S,
+        message: PhantomData<M>,
+    }
+
+    impl<'a, S, M> MaybeMessage<'a, S, M> {
+        pub fn new(state: &'a mut S) -> Self {
+            Self {
+                state,
+                message: PhantomData,
+            }
+        }
+    }
+
+    /// Implemented for states that receive the message
+    pub trait MaybeReceive<M> {
+        fn receives(self) -> bool;
+        fn try_receive(self, message: M) -> Result<(), M>;
+    }
+
+    impl<S: ReceiveMessage<M>, M> MaybeReceive<M> for MaybeMessage<'_, S, M> {
+        fn receives(self) -> bool {
+            true
+        }
+
+        fn try_receive(self, message: M) -> Result<(), M> {
+            self.state.receive_message(message);
+            Ok(())
+        }
+    }
+
+    /// Implemented for states that return the message
+    pub trait MaybeReturn<M> {
+        fn try_return(self) -> Option<Option<M>>;
+    }
+
+    impl<S: ReturnMessage<M>, M> MaybeReturn<M> for MaybeMessage<'_, S, M> {
+        fn try_return(self) -> Option<Option<M>> {
+            Some(self.state.return_message())
+        }
+    }
+
+    /// The fallback for states that neither receive nor return the message
+    pub trait MaybeNotSupported<M> {
+        fn receives(self) -> bool;
+        fn try_receive(self, message: M) -> Result<(), M>;
+        fn try_return(self) -> Option<Option<M>>;
+    }
+
+    impl<S, M> MaybeNotSupported<M> for &MaybeMessage<'_, S, M> {
+        fn receives(self) -> bool {
+            false
+        }
+
+        fn try_receive(self, message: M) -> Result<(), M> {
+            Err(message)
+        }
+
+        fn try_return(self) -> Option<Option<M>> {
+            None
+        }
+    }
+
+    /// Finds out whether a state receives a message like ``` MaybeMessage ```, but without an
+    /// instance of the state. The queue uses it to reject messages broadcast to all states that no
+    /// state receives, as they could never be delivered.
+    pub struct MaybeReceiver<S, M> {
+        state: PhantomData<S>,
+        message: PhantomData<M>,
+    }
+
+    impl<S, M> MaybeReceiver<S, M> {
+        pub fn new() -> Self {
+            Self {
+                state: PhantomData,
+                message: PhantomData,
+            }
+        }
+    }
+
+    impl<S, M> Default for MaybeReceiver<S, M> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    /// Implemented for states that receive the message
+    pub trait MaybeReceiveType<M> {
+        fn receives(self) -> bool;
+    }
+
+    impl<S: ReceiveMessage<M>, M> MaybeReceiveType<M> for MaybeReceiver<S, M> {
+        fn receives(self) -> bool {
+            true
+        }
+    }
+
+    /// The fallback for states that do not receive the message
+    pub trait MaybeNotReceiveType<M> {
+        fn receives(self) -> bool;
+    }
+
+    impl<S, M> MaybeNotReceiveType<M> for &MaybeReceiver<S, M> {
+        fn receives(self) -> bool {
+            false
+        }
+    }
 }
 
 
@@ -72,6 +541,36 @@ pub trait ReceiveMessage<Message> {
     fn receive_message(&mut self, message: Message);
 }
 
+/// Trait to handle an incoming message in a fallible state machine
+///
+/// Implement this trait for a state that can fail to receive a message, which is added with the
+/// #[sfsm(fallible)] marker to the add_message! definition. If an error is returned, the state
+/// machine transits into the error state, just like it does for an error of ``` try_execute ```.
+pub trait TryReceiveMessage<Message>: TryState {
+    /// Receive a message when a message for the corresponding state is pushed into the state
+    /// machine
+    ///
+    /// ```rust
+    /// # use sfsm_base::fallible::TryState;
+    /// # use sfsm_base::message::TryReceiveMessage;
+    /// # struct Message { speed: u32 };
+    /// # struct State {};
+    /// # impl TryState for State {
+    /// #     type Error = ();
+    /// # }
+    /// #
+    /// # impl TryReceiveMessage<Message> for State {
+    ///     fn try_receive_message(&mut self, message: Message) -> Result<(), Self::Error> {
+    ///         if message.speed > 100 {
+    ///             return Err(());
+    ///         }
+    ///         Ok(())
+    ///     }
+    /// # }
+    /// ```
+    fn try_receive_message(&mut self, message: Message) -> Result<(), Self::Error>;
+}
+
 /// Trait to handle an outgoing message
 ///
 /// Implement this trait for a state that can return a message according to the add_message
@@ -96,4 +595,28 @@ pub trait ReturnMessage<Message> {
     ///
     /// ```
     fn return_message(&mut self) -> Option<Message>;
-}
\ No newline at end of file
+}
+/// Trait to answer a request
+///
+/// Implement this trait for a state that answers a request according to the add_message
+/// definition, like ``` Query => Reply @ State ```.
+/// Note: for the state to actually be able to answer a request, the request has to be added with
+/// the add_message! macro.
+pub trait ReplyMessage<Request, Reply> {
+    /// Answer a request that is sent to the state machine
+    ///
+    /// ```rust
+    /// # use sfsm_base::message::ReplyMessage;
+    /// # struct Query { sensor: u8 };
+    /// # struct Reply { value: u32 };
+    /// # struct State {};
+    /// #
+    /// # impl ReplyMessage<Query, Reply> for State {
+    ///     fn reply_message(&mut self, request: Query) -> Reply {
+    ///         println!("Reading sensor {}", request.sensor);
+    ///         return Reply { value: 42 };
+    ///     }
+    /// # }
+    /// ```
+    fn reply_message(&mut self, request: Request) -> Reply;
+}
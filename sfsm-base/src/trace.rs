@@ -0,0 +1,668 @@
+use core::fmt;
+use core::ops::{BitAnd, BitOr, BitOrAssign};
+use crate::TransitGuard;
+
+/// A mask to select which trace points are traced by a state machine.
+///
+/// The trace features decide which trace points are compiled in. The mask then allows to select
+/// during runtime which of them should actually be traced. It can be set for each state machine
+/// instance individually with the ``` TraceControl ``` trait. By default, all trace points are
+/// enabled. The mask only applies to the logs. Records are always passed on to the function
+/// registered with ``` #[sfsm_record] ```.
+/// ```rust
+/// # use sfsm_base::trace::TraceMask;
+/// let mask = TraceMask::TRANSITIONS | TraceMask::MESSAGES;
+/// assert!(mask.contains(TraceMask::TRANSITIONS));
+/// assert!(!mask.contains(TraceMask::STEPS));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TraceMask(u8);
+
+impl TraceMask {
+    /// Disables all trace points
+    pub const NONE: TraceMask = TraceMask(0);
+    /// Start, stop, transitions, entries and exits. Compiled in with the ``` trace ``` feature.
+    pub const TRANSITIONS: TraceMask = TraceMask(1 << 0);
+    /// Executes. Compiled in with the ``` trace-steps ``` feature.
+    pub const STEPS: TraceMask = TraceMask(1 << 1);
+    /// Message pushes and polls. Compiled in with the ``` trace-messages ``` feature.
+    pub const MESSAGES: TraceMask = TraceMask(1 << 2);
+    /// Guard evaluations. Compiled in with the ``` trace-guards ``` feature.
+    pub const GUARDS: TraceMask = TraceMask(1 << 3);
+    /// Enables all trace points
+    pub const ALL: TraceMask = TraceMask(0xFF);
+
+    /// Checks if all trace points of ``` other ``` are enabled in the mask.
+    pub const fn contains(self, other: TraceMask) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the mask without the trace points of ``` other ```.
+    /// ```rust
+    /// # use sfsm_base::trace::TraceMask;
+    /// let mask = TraceMask::ALL.without(TraceMask::STEPS);
+    /// assert!(!mask.contains(TraceMask::STEPS));
+    /// ```
+    pub const fn without(self, other: TraceMask) -> TraceMask {
+        TraceMask(self.0 & !other.0)
+    }
+
+    /// Creates a mask from the raw bits of ``` bits() ```.
+    pub const fn from_bits(bits: u8) -> TraceMask {
+        TraceMask(bits)
+    }
+
+    /// Returns the raw bits of the mask.
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+}
+
+impl Default for TraceMask {
+    fn default() -> Self {
+        TraceMask::ALL
+    }
+}
+
+impl BitOr for TraceMask {
+    type Output = TraceMask;
+
+    fn bitor(self, rhs: TraceMask) -> TraceMask {
+        TraceMask(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for TraceMask {
+    type Output = TraceMask;
+
+    fn bitand(self, rhs: TraceMask) -> TraceMask {
+        TraceMask(self.0 & rhs.0)
+    }
+}
+
+impl BitOrAssign for TraceMask {
+    fn bitor_assign(&mut self, rhs: TraceMask) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Describes a state machine in the records. The ids of the states are their indices in the list
+/// of states the state machine was defined with.
+#[derive(Debug, PartialEq)]
+pub struct MachineInfo {
+    /// The name of the state machine
+    pub name: &'static str,
+    /// The names of all states, indexed by their ids
+    pub states: &'static [&'static str],
+    /// The id of the error state if it is a fallible state machine
+    pub error_state: Option<u8>,
+}
+
+impl MachineInfo {
+    /// Returns the name of the state with the given id.
+    /// ```rust
+    /// # use sfsm_base::trace::MachineInfo;
+    /// let info = MachineInfo { name: "Rocket", states: &["WaitForLaunch", "Launch"], error_state: None };
+    /// assert_eq!(info.state_name(1), "Launch");
+    /// assert_eq!(info.state_name(2), "?");
+    /// ```
+    pub fn state_name(&self, id: u8) -> &'static str {
+        self.states.get(id as usize).copied().unwrap_or("?")
+    }
+}
+
+/// Describes a message in the records. The ids of the messages are their indices in the list of
+/// messages they were defined with in the ``` add_messages! ``` macro.
+#[derive(Debug, PartialEq)]
+pub struct MessageInfo {
+    /// The id of the message
+    pub id: u8,
+    /// The name of the message
+    pub name: &'static str,
+}
+
+/// The hooks of a state that can return an error in a fallible state machine.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceHook {
+    /// The entry function of the state
+    Entry = 0,
+    /// The execute function of the state
+    Execute = 1,
+    /// The exit function of the state
+    Exit = 2,
+    /// The action function of a transition out of the state
+    Action = 3,
+    /// The function of the state that receives a fallible message
+    Receive = 4,
+}
+
+impl TraceHook {
+    /// Returns the hook with the given number
+    pub fn from_u8(hook: u8) -> Option<TraceHook> {
+        match hook {
+            0 => Some(TraceHook::Entry),
+            1 => Some(TraceHook::Execute),
+            2 => Some(TraceHook::Exit),
+            3 => Some(TraceHook::Action),
+            4 => Some(TraceHook::Receive),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the function that failed
+    pub fn name(&self) -> &'static str {
+        match self {
+            TraceHook::Entry => "entry",
+            TraceHook::Execute => "execute",
+            TraceHook::Exit => "exit",
+            TraceHook::Action => "action",
+            TraceHook::Receive => "receive",
+        }
+    }
+}
+
+/// The kinds of trace points. With the ``` trace-binary ``` feature, each trace point is emitted as a
+/// frame that starts with the two byte id of the state machine in little endian and the kind. The
+/// ids of the states, messages, guard results or hooks follow, as described by the variants.
+/// If the ``` trace-time ``` feature is enabled as well, the ``` TIME_FLAG ``` is set in the kind
+/// and the timestamp and the step follow as LEB128 encoded variable length integers.
+/// ```rust
+/// # use sfsm_base::trace::TraceKind;
+/// assert_eq!(TraceKind::from_u8(4), Some(TraceKind::Transit));
+/// assert_eq!(TraceKind::Transit.args(), 2);
+/// ```
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceKind {
+    /// The state machine has been started. Followed by the state.
+    Start = 0,
+    /// The state machine has been stopped.
+    Stop = 1,
+    /// A state has been entered. Followed by the state.
+    Enter = 2,
+    /// A state has been exited. Followed by the state.
+    Exit = 3,
+    /// A transition has been made. Followed by the state it started from and the target state.
+    Transit = 4,
+    /// A state has been executed. Followed by the state.
+    Execute = 5,
+    /// A message has been pushed. Followed by the message and the state.
+    Push = 6,
+    /// A message has been polled. Followed by the message and the state.
+    Poll = 7,
+    /// A guard has been evaluated. Followed by the two states and 1 if the guard returned
+    /// ``` TransitGuard::Transit ``` or 0 otherwise.
+    Guard = 8,
+    /// The error state has been entered. Followed by the state and the hook that failed.
+    Error = 9,
+}
+
+impl TraceKind {
+    /// Set in the kind if the frame ends with the timestamp and the step
+    pub const TIME_FLAG: u8 = 0x80;
+
+    /// Returns the kind with the given number
+    pub fn from_u8(kind: u8) -> Option<TraceKind> {
+        match kind {
+            0 => Some(TraceKind::Start),
+            1 => Some(TraceKind::Stop),
+            2 => Some(TraceKind::Enter),
+            3 => Some(TraceKind::Exit),
+            4 => Some(TraceKind::Transit),
+            5 => Some(TraceKind::Execute),
+            6 => Some(TraceKind::Push),
+            7 => Some(TraceKind::Poll),
+            8 => Some(TraceKind::Guard),
+            9 => Some(TraceKind::Error),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the kind as it is used in the logs
+    pub fn name(&self) -> &'static str {
+        match self {
+            TraceKind::Start => "Start",
+            TraceKind::Stop => "Stop",
+            TraceKind::Enter => "Enter",
+            TraceKind::Exit => "Exit",
+            TraceKind::Transit => "Transit",
+            TraceKind::Execute => "Execute",
+            TraceKind::Push => "Push",
+            TraceKind::Poll => "Poll",
+            TraceKind::Guard => "Guard",
+            TraceKind::Error => "Enter error state",
+        }
+    }
+
+    /// Returns the number of bytes following the kind in a frame
+    pub fn args(&self) -> usize {
+        match self {
+            TraceKind::Stop => 0,
+            TraceKind::Start | TraceKind::Enter | TraceKind::Exit | TraceKind::Execute => 1,
+            TraceKind::Transit | TraceKind::Push | TraceKind::Poll | TraceKind::Error => 2,
+            TraceKind::Guard => 3,
+        }
+    }
+}
+
+/// Calculates the id of a state machine from its name. The ids identify the state machines in the
+/// binary traces and are listed in their dictionaries.
+/// ```rust
+/// # use sfsm_base::trace::machine_id;
+/// assert_ne!(machine_id("Rocket"), machine_id("Radio"));
+/// ```
+pub const fn machine_id(name: &str) -> u16 {
+    // 32 bit FNV-1a, folded to 16 bits
+    let bytes = name.as_bytes();
+    let mut hash: u32 = 0x811c9dc5;
+    let mut index = 0;
+    while index < bytes.len() {
+        hash ^= bytes[index] as u32;
+        hash = hash.wrapping_mul(0x01000193);
+        index += 1;
+    }
+    ((hash >> 16) ^ (hash & 0xFFFF)) as u16
+}
+
+/// Formats values like the errors of fallible state machines or the message payloads in the traces.
+/// With the ``` trace-errors ``` and ``` trace-payloads ``` features, the values are traced with this
+/// trait if it is implemented and with ``` Debug ``` otherwise. Values that implement neither are
+/// traced as ``` .. ```. Implement it for values that do not implement ``` Debug ``` or should be
+/// traced differently, for example to keep the code size small on no_std targets.
+/// ```rust
+/// # use sfsm_base::trace::TraceFormat;
+/// # use core::fmt;
+/// enum RocketMalfunction {
+///     BoostersFellOff(u8),
+/// }
+///
+/// impl TraceFormat for RocketMalfunction {
+///     fn trace_format(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         match self {
+///             RocketMalfunction::BoostersFellOff(count) => write!(f, "{} boosters fell off", count),
+///         }
+///     }
+/// }
+/// ```
+pub trait TraceFormat {
+    /// Formats the value for the traces
+    fn trace_format(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+/// The events that are recorded by the state machines. All states are referred to by their ids.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceEvent {
+    /// The state machine has been started in the state
+    Start { state: u8 },
+    /// The state machine has been stopped while in the state
+    Stop { state: u8 },
+    /// The state machine transited from one state to another
+    Transit { from: u8, to: u8 },
+    /// A fallible state machine entered the error state because a hook of the state returned an error
+    Error { state: u8, hook: TraceHook },
+    /// The guard of the transition from one state to another has been evaluated
+    Guard { from: u8, to: u8, guard: TransitGuard },
+    /// A message has been pushed into the state
+    Push { message: &'static MessageInfo, state: u8 },
+    /// A message has been polled from the state
+    Poll { message: &'static MessageInfo, state: u8 },
+}
+
+/// A record of an event. It is passed to the function registered with ``` #[sfsm_record] ```.
+///
+/// The step counts the calls to the step function of the state machine and allows to tell
+/// which events happened during the same step. If the ``` trace-time ``` feature is enabled, the
+/// records contain the time of the clock registered with ``` #[sfsm_clock] ``` as well.
+///
+/// The machine info is shared by all instances of a state machine. To tell the instances apart,
+/// give them distinct ids with ``` TraceControl::set_trace_instance ```.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceRecord {
+    /// The state machine that recorded the event
+    pub machine: &'static MachineInfo,
+    /// The id of the state machine instance. 0 unless it has been set.
+    pub instance: u16,
+    /// The number of steps the state machine had executed when the event occurred
+    pub step: u32,
+    /// The time the event occurred at
+    pub timestamp: Option<u64>,
+    /// The recorded event
+    pub event: TraceEvent,
+}
+
+/// Formats the record in the same way as the logs. The instance id is appended to the name of the
+/// state machine, unless it is 0.
+/// ```rust
+/// # use sfsm_base::trace::{MachineInfo, TraceRecord, TraceEvent};
+/// # static ROCKET: MachineInfo = MachineInfo { name: "Rocket", states: &["WaitForLaunch", "Launch"], error_state: None };
+/// let record = TraceRecord { machine: &ROCKET, instance: 0, step: 3, timestamp: None, event: TraceEvent::Transit { from: 0, to: 1 } };
+/// assert_eq!(format!("{}", record), "Rocket #3: Transit - From WaitForLaunch to Launch");
+/// let record = TraceRecord { timestamp: Some(1200), ..record };
+/// assert_eq!(format!("{}", record), "[1200] Rocket #3: Transit - From WaitForLaunch to Launch");
+/// let record = TraceRecord { instance: 2, ..record };
+/// assert_eq!(format!("{}", record), "[1200] Rocket[2] #3: Transit - From WaitForLaunch to Launch");
+/// ```
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let machine = self.machine;
+        if let Some(timestamp) = self.timestamp {
+            write!(f, "[{}] ", timestamp)?;
+        }
+        write!(f, "{}", machine.name)?;
+        if self.instance != 0 {
+            write!(f, "[{}]", self.instance)?;
+        }
+        write!(f, " #{}: ", self.step)?;
+        match self.event {
+            TraceEvent::Start { state } => write!(f, "Start - {}", machine.state_name(state)),
+            TraceEvent::Stop { state } => write!(f, "Stop - {}", machine.state_name(state)),
+            TraceEvent::Transit { from, to } => write!(f, "Transit - From {} to {}",
+                                                       machine.state_name(from), machine.state_name(to)),
+            TraceEvent::Guard { from, to, guard } => write!(f, "Guard - From {} to {}: {:?}",
+                                                             machine.state_name(from), machine.state_name(to), guard),
+            TraceEvent::Error { state, hook } => write!(f, "Enter error state - From {}, {} failed",
+                                                        machine.state_name(state), hook.name()),
+            TraceEvent::Push { message, state } => write!(f, "Push - {} to {}", message.name, machine.state_name(state)),
+            TraceEvent::Poll { message, state } => write!(f, "Poll - {} from {}", message.name, machine.state_name(state)),
+        }
+    }
+}
+
+/// A ring buffer that keeps the last N records.
+///
+/// Register a function with ``` #[sfsm_record] ``` that stores the records in the flight recorder
+/// to be able to tell what the state machines did before a fault occurred. As it can be created
+/// in a const context, it can be placed in a static that is read in the panic handler.
+/// ```rust
+/// # use sfsm_base::trace::{FlightRecorder, MachineInfo, TraceRecord, TraceEvent};
+/// # static ROCKET: MachineInfo = MachineInfo { name: "Rocket", states: &["WaitForLaunch", "Launch"], error_state: None };
+/// let mut recorder = FlightRecorder::<2>::new();
+/// for step in 0..3 {
+///     recorder.record(&TraceRecord { machine: &ROCKET, instance: 0, step, timestamp: None, event: TraceEvent::Transit { from: 0, to: 1 } });
+/// }
+/// let steps: Vec<u32> = recorder.iter().map(|record| record.step).collect();
+/// assert_eq!(steps, [1, 2]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct FlightRecorder<const N: usize> {
+    records: [Option<TraceRecord>; N],
+    next: usize,
+    len: usize,
+}
+
+impl<const N: usize> FlightRecorder<N> {
+    /// Creates an empty flight recorder
+    pub const fn new() -> Self {
+        Self {
+            records: [None; N],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /// Stores the record. If the recorder is full, the oldest record is overwritten.
+    pub fn record(&mut self, record: &TraceRecord) {
+        if N == 0 {
+            return;
+        }
+        self.records[self.next] = Some(*record);
+        self.next = (self.next + 1) % N;
+        if self.len < N {
+            self.len += 1;
+        }
+    }
+
+    /// Iterates over the stored records, starting with the oldest one.
+    pub fn iter(&self) -> impl Iterator<Item = &TraceRecord> + '_ {
+        let start = (self.next + N - self.len) % N.max(1);
+        (0..self.len).filter_map(move |index| self.records[(start + index) % N].as_ref())
+    }
+
+    /// Returns the most recent record
+    pub fn latest(&self) -> Option<&TraceRecord> {
+        self.iter().last()
+    }
+
+    /// Returns the number of stored records
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if no records are stored
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes all records
+    pub fn clear(&mut self) {
+        self.records = [None; N];
+        self.next = 0;
+        self.len = 0;
+    }
+}
+
+impl<const N: usize> Default for FlightRecorder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub mod __protected {
+    use core::fmt;
+    use crate::__protected::StateMachine;
+    use crate::trace::{TraceFormat, TraceMask};
+
+    /// Holds the tracing state of a state machine instance. It is stored by the generated state
+    /// machines if tracing is enabled and should not be used manually.
+    #[derive(Clone, Copy, Debug)]
+    pub struct TraceContext {
+        /// The trace points that are traced
+        pub mask: TraceMask,
+        /// The number of executed steps
+        pub step: u32,
+        /// The id of the instance that is put into the records
+        pub instance: u16,
+    }
+
+    impl TraceContext {
+        /// Creates a context with all trace points enabled
+        pub const fn new() -> Self {
+            Self {
+                mask: TraceMask::ALL,
+                step: 0,
+                instance: 0,
+            }
+        }
+    }
+
+    impl Default for TraceContext {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    /// The TraceControl trait will be implemented for every state machine and allows to change
+    /// which trace points are traced during runtime.
+    pub trait TraceControl: StateMachine {
+        /// Sets the trace mask of this state machine instance. Only the trace points that are
+        /// both compiled in and enabled in the mask will be traced.
+        ///```rust,ignore
+        /// use sfsm_base::{TraceControl, TraceMask};
+        /// sfsm.set_trace_mask(TraceMask::TRANSITIONS | TraceMask::STEPS);
+        ///```
+        fn set_trace_mask(&mut self, mask: TraceMask);
+
+        /// Returns the trace mask of this state machine instance. If no trace feature is enabled,
+        /// the state machine does not store a mask and ``` TraceMask::NONE ``` is returned.
+        fn trace_mask(&self) -> TraceMask;
+
+        /// Sets the id of this state machine instance. All instances of a state machine share the
+        /// same ``` MachineInfo ```, so the id is put into the records to tell them apart. It is 0
+        /// by default.
+        ///```rust,ignore
+        /// left_engine.set_trace_instance(1);
+        /// right_engine.set_trace_instance(2);
+        ///```
+        fn set_trace_instance(&mut self, instance: u16);
+
+        /// Returns the id of this state machine instance. If no trace feature is enabled, the state
+        /// machine does not store an id and 0 is returned.
+        fn trace_instance(&self) -> u16;
+    }
+
+    /// Wraps a value that is formatted for the traces. The generated code calls ``` format_trace ```
+    /// on a triple reference to it. This picks the ``` TraceFormat ``` implementation of the value
+    /// if there is one, falls back to its ``` Debug ``` implementation and otherwise writes ``` .. ```.
+    pub struct TraceValue<'a, T>(pub &'a T);
+
+    /// Formats the value with its ``` TraceFormat ``` implementation
+    pub trait FormatWithTraceFormat {
+        fn format_trace(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+    }
+
+    impl<T: TraceFormat> FormatWithTraceFormat for &&TraceValue<'_, T> {
+        fn format_trace(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.trace_format(f)
+        }
+    }
+
+    /// Formats the value with its ``` Debug ``` implementation
+    pub trait FormatWithDebug {
+        fn format_trace(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+    }
+
+    impl<T: fmt::Debug> FormatWithDebug for &TraceValue<'_, T> {
+        fn format_trace(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt::Debug::fmt(self.0, f)
+        }
+    }
+
+    /// Formats values that can not be formatted as ``` .. ```
+    pub trait FormatWithoutFormat {
+        fn format_trace(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+    }
+
+    impl<T> FormatWithoutFormat for TraceValue<'_, T> {
+        fn format_trace(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("..")
+        }
+    }
+
+    /// Implements ``` Display ``` with the function, so values can be passed to the trace backends.
+    pub struct TraceDisplay<F: Fn(&mut fmt::Formatter<'_>) -> fmt::Result>(pub F);
+
+    impl<F: Fn(&mut fmt::Formatter<'_>) -> fmt::Result> fmt::Display for TraceDisplay<F> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            (self.0)(f)
+        }
+    }
+
+    /// A fixed size buffer the logs are formatted into if they contain values. Longer logs are
+    /// truncated.
+    pub struct TraceBuffer {
+        buffer: [u8; TraceBuffer::SIZE],
+        len: usize,
+    }
+
+    impl TraceBuffer {
+        /// The maximum length of a formatted log
+        pub const SIZE: usize = 128;
+
+        /// Creates an empty buffer
+        pub const fn new() -> Self {
+            Self {
+                buffer: [0; TraceBuffer::SIZE],
+                len: 0,
+            }
+        }
+
+        /// Returns the formatted log
+        pub fn as_str(&self) -> &str {
+            // Only whole characters are written into the buffer
+            core::str::from_utf8(&self.buffer[..self.len]).unwrap_or("")
+        }
+    }
+
+    impl Default for TraceBuffer {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl fmt::Write for TraceBuffer {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let mut len = s.len().min(TraceBuffer::SIZE - self.len);
+            while !s.is_char_boundary(len) {
+                len -= 1;
+            }
+            self.buffer[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+            self.len += len;
+            if len < s.len() {
+                Err(fmt::Error)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    /// A frame of the binary traces that contains a timestamp and a step, so its length is only
+    /// known during runtime.
+    pub struct TraceFrame {
+        buffer: [u8; TraceFrame::SIZE],
+        len: usize,
+    }
+
+    impl TraceFrame {
+        /// The maximum length of a frame: the header and the ids, a 64 bit and a 32 bit varint
+        pub const SIZE: usize = 6 + 10 + 5;
+
+        /// Creates a frame that starts with the bytes
+        pub fn new(bytes: &[u8]) -> Self {
+            let mut frame = Self {
+                buffer: [0; TraceFrame::SIZE],
+                len: bytes.len(),
+            };
+            frame.buffer[..bytes.len()].copy_from_slice(bytes);
+            frame
+        }
+
+        /// Appends the value as LEB128 encoded variable length integer
+        pub fn push_varint(&mut self, mut value: u64) {
+            loop {
+                let byte = (value & 0x7F) as u8;
+                value >>= 7;
+                if value == 0 {
+                    self.buffer[self.len] = byte;
+                    self.len += 1;
+                    return;
+                }
+                self.buffer[self.len] = byte | 0x80;
+                self.len += 1;
+            }
+        }
+
+        /// Returns the encoded frame
+        pub fn as_bytes(&self) -> &[u8] {
+            &self.buffer[..self.len]
+        }
+    }
+
+    /// Compares the names at compile time. Used to check that the parent of a nested state machine
+    /// is the path the parent is traced with.
+    pub const fn trace_names_match(name: &str, other: &str) -> bool {
+        let name = name.as_bytes();
+        let other = other.as_bytes();
+        if name.len() != other.len() {
+            return false;
+        }
+        let mut index = 0;
+        while index < name.len() {
+            if name[index] != other[index] {
+                return false;
+            }
+            index += 1;
+        }
+        true
+    }
+}
@@ -0,0 +1,13 @@
+use std::env;
+
+fn main() {
+    // The test decodes its own frames, so it needs the defmt table in the binary. The interned
+    // strings are not loaded, so the test is not position independent.
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("linux") {
+        let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+        println!("cargo:rustc-link-arg-tests=-T{}/defmt.x", manifest_dir);
+        println!("cargo:rustc-link-arg-tests=-no-pie");
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=defmt.x");
+}
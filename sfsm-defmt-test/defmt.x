@@ -0,0 +1,33 @@
+/* The sections of the defmt linker script for the defmt test on the host. Unlike the one from the */
+/* defmt crate, it is inserted into the default layout of the host instead of replacing it. */
+SECTIONS
+{
+  .defmt 1 (INFO) :
+  {
+    . = 1;
+
+    *(.defmt.prim.*);
+
+    __DEFMT_MARKER_TRACE_START = .;
+    *(.defmt.trace.*);
+    __DEFMT_MARKER_TRACE_END = .;
+    __DEFMT_MARKER_DEBUG_START = .;
+    *(.defmt.debug.*);
+    __DEFMT_MARKER_DEBUG_END = .;
+    __DEFMT_MARKER_INFO_START = .;
+    *(.defmt.info.*);
+    __DEFMT_MARKER_INFO_END = .;
+    __DEFMT_MARKER_WARN_START = .;
+    *(.defmt.warn.*);
+    __DEFMT_MARKER_WARN_END = .;
+    __DEFMT_MARKER_ERROR_START = .;
+    *(.defmt.error.*);
+    __DEFMT_MARKER_ERROR_END = .;
+
+    *(.defmt.*);
+
+    __DEFMT_MARKER_END = .;
+
+    KEEP(*(.defmt.end .defmt.end.*));
+  }
+} INSERT AFTER .comment;
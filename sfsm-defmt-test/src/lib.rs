@@ -0,0 +1,2 @@
+//! Checks that the trace points of the trace-defmt feature emit frames that decode into the
+//! expected lines with the defmt table. The test is in tests/decode.rs.
@@ -0,0 +1,106 @@
+use sfsm::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use defmt_decoder::{DecodeError, Table};
+
+// Runs the launch sequence of the defmt example and decodes the captured frames with the defmt
+// table linked into the test binary by the build script.
+
+/// Capture the frames instead of sending them to a probe
+static FRAMES: AtomicUsize = AtomicUsize::new(0);
+static ENCODER: Mutex<defmt::Encoder> = Mutex::new(defmt::Encoder::new());
+static CAPTURED: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+fn capture(bytes: &[u8]) {
+    CAPTURED.lock().unwrap().extend_from_slice(bytes);
+}
+
+#[defmt::global_logger]
+struct Logger;
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        FRAMES.fetch_add(1, Ordering::SeqCst);
+        ENCODER.lock().unwrap().start_frame(capture);
+    }
+    unsafe fn flush() {}
+    unsafe fn release() {
+        ENCODER.lock().unwrap().end_frame(capture);
+    }
+    unsafe fn write(bytes: &[u8]) {
+        ENCODER.lock().unwrap().write(bytes, capture);
+    }
+}
+
+defmt::timestamp!("{=usize}", FRAMES.load(Ordering::SeqCst));
+
+struct WaitForLaunch {
+    do_launch: bool,
+}
+struct Launch {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+
+derive_transition_into!(WaitForLaunch, Launch);
+impl Transition<Launch> for WaitForLaunch {
+    fn guard(&self) -> TransitGuard {
+        self.do_launch.into()
+    }
+}
+
+fn run_launch_sequence() -> Result<(), SfsmError> {
+    let mut rocket = Rocket::new();
+
+    rocket.start(WaitForLaunch { do_launch: true })?;
+    let frames_after_start = FRAMES.load(Ordering::SeqCst);
+    assert!(frames_after_start > 0);
+
+    rocket.step()?;
+    assert!(IsState::<Launch>::is_state(&rocket));
+    assert!(FRAMES.load(Ordering::SeqCst) > frames_after_start);
+
+    rocket.stop()?;
+    Ok(())
+}
+
+/// Decodes the frames into the logged lines with the table in the test binary, the same way
+/// the frames of a target are decoded with the table in its firmware.
+fn decode(bytes: &[u8]) -> Vec<String> {
+    let binary = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+    let table = Table::parse(&binary).unwrap().expect("The binary must contain the defmt table");
+    let mut decoder = table.new_stream_decoder();
+    decoder.received(bytes);
+
+    let mut lines = Vec::new();
+    loop {
+        match decoder.decode() {
+            Ok(frame) => lines.push(frame.display_message().to_string()),
+            Err(DecodeError::UnexpectedEof) => return lines,
+            Err(DecodeError::Malformed) => panic!("The frames must be well formed"),
+        }
+    }
+}
+
+#[test]
+#[cfg_attr(not(target_os = "linux"), ignore)]
+fn launch_sequence_with_defmt() {
+    run_launch_sequence().unwrap();
+
+    let bytes = CAPTURED.lock().unwrap().clone();
+    let lines = decode(&bytes);
+    for expected in ["Rocket: Start - WaitForLaunch", "Rocket: Exit - WaitForLaunch",
+                     "Rocket: Transit - From WaitForLaunch to Launch", "Rocket: Enter - Launch",
+                     "Rocket: Stop"].iter() {
+        assert!(lines.iter().any(|line| line == expected), "{} was not logged", expected);
+    }
+}
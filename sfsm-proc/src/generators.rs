@@ -1,12 +1,21 @@
-use quote::{quote, ToTokens};
-use crate::{types::{Machine, State, Messages, StateMessage, MessageDir, Mode}};
+use quote::{quote, quote_spanned, ToTokens};
+use crate::{types::{Machine, State, Messages, Message, StateMessage, MessageDir, MessageTargets, Mode, Broadcast, Route, Routes}};
 use proc_macro2::TokenStream;
 use crate::trace;
+use crate::trace::TracePoint;
+use sfsm_base::trace::{TraceHook, TraceKind};
 
 pub struct TransitToErrorToTokens {}
 
 impl<'a> TransitToErrorToTokens {
-    fn wrap_if_fallible(machine: &'a Machine, tokens: TokenStream, current_state: &State) -> proc_macro2::TokenStream {
+    fn wrap_if_fallible(machine: &'a Machine, tokens: TokenStream, current_state: &State, hook: TraceHook) -> proc_macro2::TokenStream {
+        TransitToErrorToTokens::wrap_with_origin(machine, tokens, current_state, current_state, hook)
+    }
+
+    /// Wraps the tokens like ``` wrap_if_fallible ```, but traces the error with another state as
+    /// its origin. The entry of a target state is wrapped for the state that is left, so an error
+    /// of the target state's entry is still handled like an error of the state that is left.
+    fn wrap_with_origin(machine: &'a Machine, tokens: TokenStream, current_state: &State, origin: &State, hook: TraceHook) -> proc_macro2::TokenStream {
         match &machine.mode {
             Mode::NonFallible { .. } => {
                 proc_macro2::TokenStream::from(quote! {
@@ -17,7 +26,12 @@ impl<'a> TransitToErrorToTokens {
                 let error_state_entry = &(machine.error_state).as_ref().expect("Internal error. Expected to have a error state.").enum_name;
                 let enum_name = &machine.enum_name;
                 let error_state = &(machine.error_state).as_ref().expect("Internal error. Expected to have a error state.");
-                let trace_error_state = trace::trace(trace::format_log(&machine.name.to_string(), "Enter error state", ""));
+                let trace_error_state = trace::trace(TracePoint::error(machine, origin, hook));
+                let origin_id = machine.state_id(origin);
+                let trace_hook = proc_macro2::Ident::new(&format!("{:?}", hook), proc_macro2::Span::call_site());
+                let record_error_state = trace::record(&machine.name, quote! {
+                    TraceEvent::Error { state: #origin_id, hook: TraceHook::#trace_hook }
+                });
                 if error_state.enum_name != current_state.enum_name {
                     let entry = &machine.trait_definitions.entry;
                     let state_trait = &machine.trait_definitions.state_trait;
@@ -25,6 +39,7 @@ impl<'a> TransitToErrorToTokens {
                     proc_macro2::TokenStream::from(quote! {
                         if let Err(err) = #tokens {
                             #trace_error_state
+                            #record_error_state
                             let mut err_state: #error_state = state.into();
                             err_state.consume_error(err);
                             #state_trait::#entry(&mut err_state).map_err(|err| {ExtendedSfsmError::Custom(err)})?;
@@ -41,6 +56,72 @@ impl<'a> TransitToErrorToTokens {
     }
 }
 
+pub struct ErrorTransitToTokens<'a> {
+    machine: &'a Machine,
+    state: &'a State,
+}
+
+impl<'a> ErrorTransitToTokens<'a> {
+    pub fn new(machine: &'a Machine, state: &'a State) -> Self {
+        Self {
+            machine,
+            state,
+        }
+    }
+}
+
+/// Transits from the state into the error state if it failed outside of a step, like when it
+/// failed to receive a message. Only fallible state machines have an error state.
+impl ToTokens for ErrorTransitToTokens<'_> {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        if let Mode::NonFallible = self.machine.mode {
+            return;
+        }
+        let sfsm_name = &self.machine.name;
+        let enum_name = &self.machine.enum_name;
+        let state = self.state;
+        let state_entry = &self.state.enum_name;
+        let trace_enter = trace::enter(&self.machine.trace_name());
+        let error_state = self.machine.error_state.as_ref().expect("Internal error. Expected to have a error state.");
+        let error_state_entry = &error_state.enum_name;
+
+        if error_state.enum_name == state.enum_name {
+            tokens.extend(quote! {
+                impl TransitToErrorState<#state> for #sfsm_name {
+                    fn transit_to_error_state(&mut self, err: <#state as TryState>::Error) -> Result<(), Self::Error> {
+                        Err(ExtendedSfsmError::Custom(err))
+                    }
+                }
+            });
+            return;
+        }
+
+        let trace_error_state = trace::trace(TracePoint::error(self.machine, state, TraceHook::Receive));
+        let state_id = self.machine.state_id(state);
+        let record_error_state = trace::record(sfsm_name, quote! {
+            TraceEvent::Error { state: #state_id, hook: TraceHook::Receive }
+        });
+        tokens.extend(quote! {
+            impl TransitToErrorState<#state> for #sfsm_name {
+                fn transit_to_error_state(&mut self, err: <#state as TryState>::Error) -> Result<(), Self::Error> {
+                    #trace_enter
+                    let state = match self.states {
+                        #enum_name::#state_entry(ref mut state_option) => state_option.take().ok_or(ExtendedSfsmError::Internal)?,
+                        _ => return Err(ExtendedSfsmError::Internal),
+                    };
+                    #trace_error_state
+                    #record_error_state
+                    let mut err_state: #error_state = state.into();
+                    err_state.consume_error(err);
+                    TryState::try_entry(&mut err_state).map_err(|err| {ExtendedSfsmError::Custom(err)})?;
+                    self.states = #enum_name::#error_state_entry(Some(err_state));
+                    Ok(())
+                }
+            }
+        });
+    }
+}
+
 pub struct StateMachineToTokens<'a> {
     machine: &'a Machine,
 }
@@ -68,6 +149,10 @@ impl ToTokens for StateMachineToTokens<'_> {
             StateToTokens::new(self.machine, state)
         }).collect();
 
+        let transit_states: Vec<StateToTokens> = self.machine.states.iter().map(|state| {
+            StateToTokens::transit(self.machine, state)
+        }).collect();
+
         let state_entries: Vec<StateEntriesToTokens> = (&self.machine.states).into_iter().map(|state| {
             StateEntriesToTokens::new(state)
         }).collect();
@@ -80,15 +165,50 @@ impl ToTokens for StateMachineToTokens<'_> {
             return IsStateToTokens::new(self.machine, state);
         }).collect();
 
+        let error_transits: Vec<ErrorTransitToTokens> = self.machine.states.iter().map(|state| {
+            ErrorTransitToTokens::new(self.machine, state)
+        }).collect();
+
         let init_state_tokens: TokenStream = TransitToErrorToTokens::wrap_if_fallible(&self.machine, proc_macro2::TokenStream::from(quote! {
                 #state_trait::#entry(&mut state)
-            }), &init_state);
+            }), &init_state, TraceHook::Entry);
 
         let sfsm_error = &self.machine.sfsm_error;
         let custom_error = &self.machine.custom_error;
 
-        let trace_start = trace::trace(trace::format_log(&sfsm_name.to_string(), "Start", &init_state.get_name_type()));
-        let trace_stop = trace::trace(trace::format_log(&sfsm_name.to_string(), "Stop", ""));
+        let trace_start = trace::trace(TracePoint::state(self.machine, TraceKind::Start, init_state));
+        let trace_stop = trace::trace(TracePoint::machine(self.machine, TraceKind::Stop));
+        let trace_enter = trace::enter(&self.machine.trace_name());
+        let trace_count_step = trace::count_step();
+        let init_state_id = self.machine.state_id(init_state);
+        let record_start = trace::record(sfsm_name, quote! {
+            TraceEvent::Start { state: #init_state_id }
+        });
+
+        let trace_context_field = trace::context_field();
+        let trace_context_init = trace::context_init();
+        let trace_context_param = trace::context_param();
+        let trace_context_arg = trace::context_arg();
+        let trace_control = trace::control(sfsm_name);
+        let trace_info = trace::info(self.machine);
+        let trace_dictionary = trace::dictionary(self.machine);
+        let trace_constants = trace::constants(self.machine);
+
+        let state_ids = StateIdsToTokens::new(self.machine);
+        let states_macro_name = Machine::states_macro_name(sfsm_name);
+        let state_types = &self.machine.states;
+        let parent = self.machine.options.parent.as_ref().map(|parent| quote! { @parent #parent });
+        let parent_check = trace::check_parent(self.machine);
+        let id_check = trace::check_id(self.machine);
+        let queue = QueueToTokens::new(self.machine);
+        let queue_field = queue.field();
+        let queue_init = queue.init();
+        let queue_deliver = queue.deliver();
+        let defer = DeferToTokens::new(self.machine);
+        let defer_field = defer.field();
+        let defer_init = defer.init();
+        let defer_deliver = defer.deliver();
+        let messages = self.machine.messages.as_ref().map(MessagesToTokens::new);
 
         let token_steam = proc_macro2::TokenStream::from(quote! {
             #(#attribute)*
@@ -96,15 +216,23 @@ impl ToTokens for StateMachineToTokens<'_> {
                 #(#state_entries)*
             }
 
+            #state_ids
+
             #(#attribute)*
             #vis struct #sfsm_name {
                 states: #enum_name,
+                #queue_field
+                #defer_field
+                #trace_context_field
             }
 
             impl #sfsm_name {
                 pub fn new() -> Self {
                     Self {
-                        states: #enum_name::#init_state_entry(None)
+                        states: #enum_name::#init_state_entry(None),
+                        #queue_init
+                        #defer_init
+                        #trace_context_init
                     }
                 }
             }
@@ -115,26 +243,34 @@ impl ToTokens for StateMachineToTokens<'_> {
                 type StatesEnum = #enum_name;
 
                 fn start(&mut self, mut state: Self::InitialState) -> Result<(), Self::Error> {
+                    #trace_enter
                     #[inline(always)]
-                    fn run_state(mut state: #init_state) -> Result<#enum_name, #sfsm_error#custom_error> {
+                    fn run_state(mut state: #init_state #trace_context_param) -> Result<#enum_name, #sfsm_error#custom_error> {
                         #init_state_tokens
                         Ok(#enum_name::#init_state_entry(Some(state)))
                     }
-                    self.states = run_state(state)?;
+                    self.states = run_state(state #trace_context_arg)?;
                     #trace_start
+                    #record_start
+                    #defer_deliver
                     Ok(())
                 }
 
                 fn step(&mut self) -> Result<(), Self::Error> {
+                    #trace_count_step
+                    #trace_enter
+                    #queue_deliver
                     use #enum_name::*;
                     let ref mut e = self.states;
                     *e = match *e {
                         #( #states, )*
                     };
+                    #defer_deliver
                     Ok(())
                 }
 
                 fn stop(mut self) -> Result<Self::StatesEnum, Self::Error> {
+                    #trace_enter
                     #trace_stop
                     match self.states {
                         # ( #exits )*,
@@ -146,14 +282,258 @@ impl ToTokens for StateMachineToTokens<'_> {
                 }
             }
 
+            impl Transit for #sfsm_name {
+                fn transit(&mut self) -> Result<(), Self::Error> {
+                    #trace_enter
+                    use #enum_name::*;
+                    let ref mut e = self.states;
+                    *e = match *e {
+                        #( #transit_states, )*
+                    };
+                    #defer_deliver
+                    Ok(())
+                }
+            }
+
             // Implement the is_state checks
             #(#is_states)*
+
+            #(#error_transits)*
+
+            #queue
+
+            #defer
+
+            // Passes the states back to add_messages! for the messages that are broadcast to all
+            // states. add_messages! passes the path it is called back with.
+            #[doc(hidden)]
+            #[allow(unused_macros)]
+            macro_rules! #states_macro_name {
+                ($($callback:tt)::+ ; $($messages:tt)*) => {
+                    $($callback)::+ ! { @states [#(#state_types),*] #parent $($messages)* }
+                }
+            }
+            #[doc(hidden)]
+            #[allow(unused_imports)]
+            pub(crate) use #states_macro_name;
+
+            #messages
+
+            #trace_control
+
+            #trace_constants
+
+            #parent_check
+
+            #id_check
+
+            #trace_info
+
+            #trace_dictionary
         });
 
         tokens.extend(token_steam);
     }
 }
 
+pub struct QueueToTokens<'a> {
+    machine: &'a Machine,
+}
+
+impl<'a> QueueToTokens<'a> {
+    pub fn new(machine: &'a Machine) -> Self {
+        Self {
+            machine,
+        }
+    }
+
+    pub fn field(&self) -> TokenStream {
+        let messages_enum_name = self.machine.messages_enum_name();
+        match self.machine.options.queue {
+            Some(capacity) => quote! { message_queue: MessageQueue<#messages_enum_name, #capacity>, },
+            None => quote! {},
+        }
+    }
+
+    pub fn init(&self) -> TokenStream {
+        match self.machine.options.queue {
+            Some(_) => quote! { message_queue: MessageQueue::new(), },
+            None => quote! {},
+        }
+    }
+
+    /// Delivers the queued messages in their order to the active state. Messages that the active
+    /// state does not receive are dropped, so that messages for a state that does not become
+    /// active again can not fill up the queue.
+    pub fn deliver(&self) -> TokenStream {
+        let messages_enum_name = self.machine.messages_enum_name();
+        match self.machine.options.queue {
+            Some(_) => quote! {
+                while let Some(message) = self.message_queue.pop() {
+                    let _ = DeliverMessage::<#messages_enum_name>::deliver_message(self, message);
+                }
+            },
+            None => quote! {},
+        }
+    }
+}
+
+impl ToTokens for QueueToTokens<'_> {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        if self.machine.options.queue.is_none() {
+            return;
+        }
+        let sfsm_name = &self.machine.name;
+        let messages_enum_name = self.machine.messages_enum_name();
+        tokens.extend(quote! {
+            impl EnqueueMessage<#messages_enum_name> for #sfsm_name {
+                fn enqueue_message<M: Into<#messages_enum_name>>(&mut self, message: M) -> Result<(), MessageError<#messages_enum_name>> {
+                    let message = message.into();
+                    if !<Self as DeliverMessage<#messages_enum_name>>::receives(&message) {
+                        return Err(match self.states.active_state_id() {
+                            Some(active_state) => MessageError::not_accepted(message, active_state),
+                            None => MessageError::NotStarted(message),
+                        });
+                    }
+                    self.message_queue.push(message).map_err(MessageError::QueueIsFull)
+                }
+
+                fn dequeue_message(&mut self) -> Option<#messages_enum_name> {
+                    self.message_queue.pop()
+                }
+            }
+        });
+    }
+}
+
+pub struct DeferToTokens<'a> {
+    machine: &'a Machine,
+}
+
+impl<'a> DeferToTokens<'a> {
+    pub fn new(machine: &'a Machine) -> Self {
+        Self {
+            machine,
+        }
+    }
+
+    /// The deferred messages are held together with the is_state check of their target state
+    pub fn field(&self) -> TokenStream {
+        let sfsm_name = &self.machine.name;
+        let messages_enum_name = self.machine.messages_enum_name();
+        match self.machine.options.defer {
+            Some(capacity) => quote! { deferred_messages: MessageQueue<(fn(&#sfsm_name) -> bool, #messages_enum_name), #capacity>, },
+            None => quote! {},
+        }
+    }
+
+    pub fn init(&self) -> TokenStream {
+        match self.machine.options.defer {
+            Some(_) => quote! { deferred_messages: MessageQueue::new(), },
+            None => quote! {},
+        }
+    }
+
+    /// Delivers the deferred messages whose target state is active. The others, and the ones that
+    /// could not be delivered, are held back in their original order.
+    pub fn deliver(&self) -> TokenStream {
+        let messages_enum_name = self.machine.messages_enum_name();
+        let sfsm_error = &self.machine.sfsm_error;
+        match self.machine.options.defer {
+            Some(_) => quote! {
+                for _ in 0..self.deferred_messages.len() {
+                    if let Some((is_target_state, message)) = self.deferred_messages.pop() {
+                        let message = if is_target_state(self) {
+                            match DeliverMessage::<#messages_enum_name>::deliver_message(self, message) {
+                                Ok(()) => continue,
+                                Err(err) => err.into_message(),
+                            }
+                        } else {
+                            message
+                        };
+                        // The message has just been taken out, so there is always room to hold it again
+                        self.deferred_messages.push((is_target_state, message)).map_err(|_| #sfsm_error::Internal)?;
+                    }
+                }
+            },
+            None => quote! {},
+        }
+    }
+}
+
+impl ToTokens for DeferToTokens<'_> {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        if self.machine.options.defer.is_none() {
+            return;
+        }
+        let sfsm_name = &self.machine.name;
+        let messages_enum_name = self.machine.messages_enum_name();
+        tokens.extend(quote! {
+            impl DeferMessage<#messages_enum_name> for #sfsm_name {
+                fn defer_message<S, M: Into<#messages_enum_name>>(&mut self, message: M) -> Result<(), M>
+                    where Self: IsState<S>
+                {
+                    if self.deferred_messages.is_full() {
+                        return Err(message);
+                    }
+                    // The queue is not full, so the message is always held
+                    let _ = self.deferred_messages.push((<Self as IsState<S>>::is_state, message.into()));
+                    Ok(())
+                }
+            }
+        });
+    }
+}
+
+pub struct StateIdsToTokens<'a> {
+    machine: &'a Machine,
+}
+
+impl<'a> StateIdsToTokens<'a> {
+    pub fn new(machine: &'a Machine) -> Self {
+        Self {
+            machine,
+        }
+    }
+}
+
+impl ToTokens for StateIdsToTokens<'_> {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let enum_name = &self.machine.enum_name;
+
+        if self.machine.states.len() > u8::MAX as usize + 1 {
+            tokens.extend(quote! {
+                compile_error!("A state machine can not have more than 256 states");
+            });
+            return;
+        }
+
+        let state_entries: Vec<&proc_macro2::Ident> = self.machine.states.iter().map(|state| &state.enum_name).collect();
+        let state_ids: Vec<u8> = (0..self.machine.states.len()).map(|id| id as u8).collect();
+
+        tokens.extend(quote! {
+            impl #enum_name {
+                /// Returns the id of the state. The ids are the indices of the states in the list
+                /// of states the state machine was defined with.
+                pub fn state_id(&self) -> u8 {
+                    match self {
+                        #( #enum_name::#state_entries(_) => #state_ids, )*
+                    }
+                }
+
+                /// Returns the id of the active state or None if the state machine has not been
+                /// started.
+                pub fn active_state_id(&self) -> Option<u8> {
+                    match self {
+                        #( #enum_name::#state_entries(Some(_)) => Some(#state_ids), )*
+                        _ => None,
+                    }
+                }
+            }
+        });
+    }
+}
+
 pub struct StopToTokens<'a> {
     machine: &'a Machine,
     state: &'a State,
@@ -180,10 +560,16 @@ impl ToTokens for StopToTokens<'_> {
 
         let exit_token_stream = TransitToErrorToTokens::wrap_if_fallible(&self.machine, proc_macro2::TokenStream::from(quote! {
                 #state_trait::#exit(&mut state)
-        }), &self.state);
+        }), &self.state, TraceHook::Exit);
+
+        let state_id = self.machine.state_id(self.state);
+        let record_stop = trace::record(&self.machine.name, quote! {
+            TraceEvent::Stop { state: #state_id }
+        });
 
         let token_steam = proc_macro2::TokenStream::from(quote! {
             #enum_name::#state_entry(ref mut state_option) => {
+                #record_stop
                 let mut state = state_option.take().ok_or(#sfsm_error::Internal)?;
                 #exit_token_stream
                 #transition_actions
@@ -260,6 +646,7 @@ impl ToTokens for StateEntriesToTokens<'_> {
 pub struct StateToTokens<'a> {
     machine: &'a Machine,
     state: &'a State,
+    execute: bool,
 }
 
 impl<'a> StateToTokens<'a> {
@@ -267,6 +654,16 @@ impl<'a> StateToTokens<'a> {
         Self {
             machine,
             state,
+            execute: true,
+        }
+    }
+
+    /// Only checks the transitions of the state without executing it
+    pub fn transit(machine: &'a Machine, state: &'a State) -> Self {
+        Self {
+            machine,
+            state,
+            execute: false,
         }
     }
 }
@@ -285,16 +682,26 @@ impl<'a> ToTokens for StateToTokens<'a> {
         let state_trait = &self.machine.trait_definitions.state_trait;
         let execute = &self.machine.trait_definitions.execute;
 
-        let state_execute_tokens = TransitToErrorToTokens::wrap_if_fallible(&self.machine, proc_macro2::TokenStream::from(quote! {
-                #state_trait::#execute(&mut state)
-        }), &self.state);
+        let state_execute_tokens = if self.execute {
+            TransitToErrorToTokens::wrap_if_fallible(&self.machine, proc_macro2::TokenStream::from(quote! {
+                    #state_trait::#execute(&mut state)
+            }), &self.state, TraceHook::Execute)
+        } else {
+            quote! {}
+        };
 
-        let trace_execute = trace::step(trace::format_log(&self.machine.name.to_string(), "Execute", &self.state.get_name_type()));
+        let trace_execute = if self.execute {
+            trace::step(TracePoint::state(self.machine, TraceKind::Execute, self.state))
+        } else {
+            quote! {}
+        };
+        let trace_context_param = trace::context_param();
+        let trace_context_arg = trace::context_arg();
 
         let token_steam = proc_macro2::TokenStream::from(quote! {
                 #enum_name::#state_entry(ref mut state_option) => {
                     #[inline(always)]
-                    fn run_state(state_option: &mut Option<#state>) -> Result<#enum_name, #sfsm_error#custom_error> {
+                    fn run_state(state_option: &mut Option<#state> #trace_context_param) -> Result<#enum_name, #sfsm_error#custom_error> {
                         let mut state = state_option.take().ok_or(#sfsm_error::Internal)?;
                         #trace_execute
                         #state_execute_tokens
@@ -303,7 +710,7 @@ impl<'a> ToTokens for StateToTokens<'a> {
                             return Ok(#enum_name::#state_entry(Some(state)));
                         }
                     }
-                    run_state(state_option)?
+                    run_state(state_option #trace_context_arg)?
                 }
         });
 
@@ -353,26 +760,36 @@ impl ToTokens for TransitionToTokens<'_> {
 
         let exit_token_stream = TransitToErrorToTokens::wrap_if_fallible(&self.machine, proc_macro2::TokenStream::from(quote! {
                 #state_trait::#exit(&mut state)
-        }), &self.state);
+        }), &self.state, TraceHook::Exit);
 
         let target_state = (&self.machine.states).into_iter().find(|state| {
             return state.enum_name == *target_state_entry;
         }).expect("Internal error. Expected to find a state matching the transition");
 
-        let state_entry_tokens: TokenStream = TransitToErrorToTokens::wrap_if_fallible(&self.machine, proc_macro2::TokenStream::from(quote! {
+        let state_entry_tokens: TokenStream = TransitToErrorToTokens::wrap_with_origin(&self.machine, proc_macro2::TokenStream::from(quote! {
                 #state_trait::#entry(&mut state)
-            }), &self.state);
+            }), &self.state, self.target, TraceHook::Entry);
+
+        let trace_entry = trace::trace(TracePoint::state(self.machine, TraceKind::Enter, self.target));
+        let trace_exit = trace::trace(TracePoint::state(self.machine, TraceKind::Exit, self.state));
+        let trace_transit = trace::trace(TracePoint::transit(self.machine, self.state, self.target));
+        let from_id = self.machine.state_id(self.state);
+        let to_id = self.machine.state_id(self.target);
+        let record_transit = trace::record(&self.machine.name, quote! {
+            TraceEvent::Transit { from: #from_id, to: #to_id }
+        });
 
-        let trace_entry = trace::trace(trace::format_log(&self.machine.name.to_string(), "Enter", &self.target.get_name_type()));
-        let trace_exit = trace::trace(trace::format_log(&self.machine.name.to_string(), "Exit", &self.state.get_name_type()));
-        let trace_transit = trace::trace(trace::format_log(&self.machine.name.to_string(), "Transit", &format!("From {} to {}", &self.state.get_name_type(), &self.target.get_name_type())));
+        let guard = trace::guard(self.machine, self.state, self.target, quote! {
+            #transit_trait::<#target_state>::guard(&state)
+        });
 
         let token_steam = proc_macro2::TokenStream::from(quote! {
-            if #transit_trait::<#target_state>::guard(&state) == TransitGuard::Transit {
+            if #guard == TransitGuard::Transit {
                 #exit_token_stream
                 #exit_transitions
                 #trace_exit
                 #trace_transit
+                #record_transit
                 let mut state: #target_state = state.into();
 
                 #state_entry_tokens
@@ -410,7 +827,7 @@ impl ToTokens for ExitTransitionToTokens<'_> {
         let exit_token_streams: Vec<proc_macro2::TokenStream> = transits.into_iter().map(|transits| {
             TransitToErrorToTokens::wrap_if_fallible(&self.machine, proc_macro2::TokenStream::from(quote! {
                 #transit_trait::<#transits>::#action(&mut state)
-            }), &self.state)
+            }), &self.state, TraceHook::Action)
         }).collect();
 
         let token_steam = proc_macro2::TokenStream::from(quote! {
@@ -433,6 +850,23 @@ impl<'a> StateMessageToTokens<'a> {
             messages,
         }
     }
+
+    fn record(&self, kind: TokenStream, message: &Message) -> TokenStream {
+        record_message(self.messages, kind, message, &self.state_message.state)
+    }
+}
+
+fn record_message(messages: &Messages, kind: TokenStream, message: &Message, state: &State) -> TokenStream {
+    let enum_entry = &state.enum_name;
+    let enum_name = &messages.enum_name;
+    let message_id = messages.message_id(message);
+    let message_name = message.get_name_type();
+    trace::record(&messages.name, quote! {
+        TraceEvent::#kind {
+            message: &MessageInfo { id: #message_id, name: #message_name },
+            state: #enum_name::#enum_entry(None).state_id(),
+        }
+    })
 }
 
 impl ToTokens for StateMessageToTokens<'_> {
@@ -442,19 +876,57 @@ impl ToTokens for StateMessageToTokens<'_> {
         let state = &self.state_message.state;
         let enum_name = &self.messages.enum_name;
         let sfsm_name = &self.messages.name;
+        let trace_enter = trace::enter(&self.messages.trace_name());
+        let impl_generics = message_dir.impl_generics();
 
         let token_steam = match message_dir {
             MessageDir::Push(message) => {
                 let message_name = &message.name;
                 let message_args = &message.generics;
-                let trace_push = trace::message(trace::format_log(&self.messages.name.to_string(), "Push", &format!("{} to {}", &message.get_name_type(), &state.get_name_type())));
+                let trace_push = trace::message(TracePoint::message(self.messages, TraceKind::Push, message, "to", state,
+                                                                     quote! { Some(&message) }, self.state_message.muted));
+                let record_push = self.record(quote! {Push}, message);
+                let inactive = if self.state_message.deferred {
+                    let messages_enum_name = &self.messages.messages_enum_name;
+                    quote! {
+                        return DeferMessage::<#messages_enum_name>::defer_message::<#state, _>(self, message)
+                            .map_err(MessageError::QueueIsFull);
+                    }
+                } else {
+                    quote! {
+                        return Err(MessageError::inactive(message, self.states.active_state_id()));
+                    }
+                };
+                if self.state_message.fallible {
+                    return tokens.extend(quote! {
+                        impl #impl_generics TryPushMessage<#state, #message_name#message_args> for #sfsm_name {
+                            fn try_push_message(&mut self, message: #message_name#message_args) -> Result<(), PushTransitError<#message_name#message_args, Self::Error>> {
+                                #trace_enter
+                                let result = match self.states {
+                                    #enum_name::#enum_entry(Some(ref mut state)) => {
+                                        #trace_push
+                                        #record_push
+                                        TryReceiveMessage::<#message_name#message_args>::try_receive_message(state, message)
+                                    }
+                                    _ => return Err(PushTransitError::Message(MessageError::inactive(message, self.states.active_state_id()))),
+                                };
+                                if let Err(err) = result {
+                                    TransitToErrorState::<#state>::transit_to_error_state(self, err).map_err(PushTransitError::Transit)?;
+                                }
+                                Ok(())
+                            }
+                        }
+                    });
+                }
                 proc_macro2::TokenStream::from(quote! {
-                    impl PushMessage<#state, #message_name#message_args> for #sfsm_name {
+                    impl #impl_generics PushMessage<#state, #message_name#message_args> for #sfsm_name {
                         fn push_message(&mut self, message: #message_name#message_args) -> Result<(), MessageError<#message_name#message_args>> {
+                            #trace_enter
                             match self.states {
                                 #enum_name::#enum_entry(ref mut state_option) => {
                                     if let Some(ref mut state) = state_option {
                                         #trace_push
+                                        #record_push
                                         state.receive_message(message);
                                         return Ok(())
                                     }
@@ -463,7 +935,7 @@ impl ToTokens for StateMessageToTokens<'_> {
                                     // Do nothing, this will return and error at the end of the function
                                 }
                             }
-                            return Err(MessageError::StateIsNotActive(message));
+                            #inactive
                         }
                     }
                 })
@@ -471,16 +943,20 @@ impl ToTokens for StateMessageToTokens<'_> {
             MessageDir::Poll(message) => {
                 let message_name = &message.name;
                 let message_args = &message.generics;
-                let trace_poll = trace::message(trace::format_log(&self.messages.name.to_string(), "Poll", &format!("{} from {}", &message.get_name_type(), &state.get_name_type())));
+                let trace_poll = trace::message(TracePoint::message(self.messages, TraceKind::Poll, message, "from", state,
+                                                                     quote! { message.as_ref() }, self.state_message.muted));
+                let record_poll = self.record(quote! {Poll}, message);
                 proc_macro2::TokenStream::from(quote! {
-                    impl PollMessage<#state, #message_name#message_args> for #sfsm_name {
+                    impl #impl_generics PollMessage<#state, #message_name#message_args> for #sfsm_name {
                         fn poll_message(&mut self) -> Result<Option<#message_name#message_args>, MessageError<()>> {
+                            #trace_enter
                             match self.states {
                                 #enum_name::#enum_entry(ref mut state_option) => {
                                     if let Some(ref mut state) = state_option {
                                         let message = state.return_message();
                                         if (message.is_some()) {
                                             #trace_poll
+                                            #record_poll
                                         }
                                         return Ok(message)
                                     }
@@ -489,11 +965,46 @@ impl ToTokens for StateMessageToTokens<'_> {
                                     // Do nothing, this will return and error at the end of the function
                                 }
                             }
-                            return Err(MessageError::StateIsNotActive(()));
+                            return Err(MessageError::inactive((), self.states.active_state_id()));
                         }
                     }
                 })
             }
+            MessageDir::Request(request, reply) => {
+                let request_name = &request.name;
+                let request_args = &request.generics;
+                let reply_name = &reply.name;
+                let reply_args = &reply.generics;
+                let trace_request = trace::message(TracePoint::message(self.messages, TraceKind::Push, request, "to", state,
+                                                                        quote! { Some(&request) }, self.state_message.muted));
+                let record_request = self.record(quote! {Push}, request);
+                let trace_reply = trace::message(TracePoint::message(self.messages, TraceKind::Poll, reply, "from", state,
+                                                                      quote! { Some(&reply) }, self.state_message.muted));
+                let record_reply = self.record(quote! {Poll}, reply);
+                quote! {
+                    impl #impl_generics RequestMessage<#state, #request_name#request_args, #reply_name#reply_args> for #sfsm_name {
+                        fn request_message(&mut self, request: #request_name#request_args) -> Result<#reply_name#reply_args, MessageError<#request_name#request_args>> {
+                            #trace_enter
+                            match self.states {
+                                #enum_name::#enum_entry(ref mut state_option) => {
+                                    if let Some(ref mut state) = state_option {
+                                        #trace_request
+                                        #record_request
+                                        let reply = ReplyMessage::<#request_name#request_args, #reply_name#reply_args>::reply_message(state, request);
+                                        #trace_reply
+                                        #record_reply
+                                        return Ok(reply)
+                                    }
+                                }
+                                _ => {
+                                    // Do nothing, this will return and error at the end of the function
+                                }
+                            }
+                            return Err(MessageError::inactive(request, self.states.active_state_id()));
+                        }
+                    }
+                }
+            }
         };
 
         tokens.extend(token_steam);
@@ -510,6 +1021,30 @@ impl<'a> MessagesToTokens<'a> {
             messages,
         }
     }
+
+    /// Checks that the states of the messages are states of the state machine, by matching the
+    /// variants of the states enum. An unknown state is reported at the state in the definition.
+    fn state_check(&self) -> TokenStream {
+        let enum_name = &self.messages.enum_name;
+        let mut states: Vec<&State> = vec![];
+        let listed = self.messages.broadcasts.iter().filter(|broadcast| !broadcast.all).flat_map(|broadcast| broadcast.states.iter());
+        for state in self.messages.messages.iter().map(|message| &message.state).chain(listed) {
+            if !states.iter().any(|known| known.enum_name == state.enum_name) {
+                states.push(state);
+            }
+        }
+        let variants = states.iter().map(|state| {
+            let entry = &state.enum_name;
+            quote_spanned! { entry.span() => #enum_name::#entry(_) => {} }
+        });
+        quote! {
+            #[allow(unreachable_patterns)]
+            const _: fn(&#enum_name) = |states| match states {
+                #( #variants, )*
+                _ => {}
+            };
+        }
+    }
 }
 
 impl ToTokens for MessagesToTokens<'_> {
@@ -520,10 +1055,473 @@ impl ToTokens for MessagesToTokens<'_> {
             StateMessageToTokens::new(message, self.messages)
         }).collect();
 
+        let broadcasts: Vec<BroadcastToTokens> = self.messages.broadcasts.iter().map(|broadcast| {
+            BroadcastToTokens::new(broadcast, self.messages)
+        }).collect();
+
+        let trace_dictionary = trace::messages_dictionary(self.messages);
+        let state_check = self.state_check();
+        let parent_check = trace::check_messages_parent(self.messages);
+        let messages_enum = if self.messages.options.dispatch {
+            Some(MessagesEnumToTokens::new(self.messages))
+        } else {
+            None
+        };
+
         let token_steam = proc_macro2::TokenStream::from(quote! {
             #(#messages_to_tokens)*
+
+            #(#broadcasts)*
+
+            #messages_enum
+
+            #state_check
+
+            #parent_check
+
+            #trace_dictionary
+        });
+
+        tokens.extend(token_steam);
+    }
+}
+
+pub struct MessagesEnumToTokens<'a> {
+    messages: &'a Messages,
+}
+
+impl<'a> MessagesEnumToTokens<'a> {
+    pub fn new(messages: &'a Messages) -> Self {
+        Self {
+            messages,
+        }
+    }
+}
+
+/// Generates the enum of all messages that can be pushed into the state machine and delivers them
+/// to the active state.
+impl ToTokens for MessagesEnumToTokens<'_> {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let vis = &self.messages.visibility;
+        let sfsm_name = &self.messages.name;
+        let messages_enum_name = &self.messages.messages_enum_name;
+        let push_messages = self.messages.push_messages();
+        let poll_messages = self.messages.poll_messages();
+        let message_types = self.messages.enum_messages();
+
+        let variants: Vec<proc_macro2::Ident> = message_types.iter().map(|message| message.variant_name()).collect();
+        let types: Vec<TokenStream> = message_types.iter().map(|message| {
+            let message_name = &message.name;
+            let message_args = &message.generics;
+            quote! { #message_name#message_args }
+        }).collect();
+        let deliveries: Vec<TokenStream> = push_messages.iter().map(|(message, states)| {
+            let variant = message.variant_name();
+            let message_name = &message.name;
+            let message_args = &message.generics;
+            if self.messages.broadcast(message, true).is_some() {
+                return quote! {
+                    #messages_enum_name::#variant(message) => {
+                        PushAnyMessage::<#message_name#message_args>::push_any_message(self, message)
+                            .map_err(|err| err.map(#messages_enum_name::#variant))
+                    }
+                };
+            }
+            quote! {
+                #messages_enum_name::#variant(message) => {
+                    #(
+                        if IsState::<#states>::is_state(self) {
+                            return PushMessage::<#states, #message_name#message_args>::push_message(self, message)
+                                .map_err(|err| err.map(#messages_enum_name::#variant));
+                        }
+                    )*
+                    Err(MessageError::inactive(#messages_enum_name::#variant(message), self.states.active_state_id()))
+                }
+            }
+        }).collect();
+        // A message broadcast to all states is only received if one of the states implements
+        // ReceiveMessage for it
+        let receivers: Vec<TokenStream> = push_messages.iter().map(|(message, _)| {
+            let variant = message.variant_name();
+            let message_name = &message.name;
+            let message_args = &message.generics;
+            match self.messages.broadcast(message, true).filter(|broadcast| broadcast.all) {
+                Some(broadcast) => {
+                    let states = &broadcast.states;
+                    quote! {
+                        #messages_enum_name::#variant(_) => false #( || MaybeReceiver::<#states, #message_name#message_args>::new().receives() )*,
+                    }
+                }
+                None => quote! { #messages_enum_name::#variant(_) => true, },
+            }
+        }).collect();
+        // Messages that are only polled can never be delivered
+        let rejections: Vec<TokenStream> = message_types.iter()
+            .filter(|message| !push_messages.iter().any(|(known, _)| known.get_name_type() == message.get_name_type()))
+            .map(|message| {
+                let variant = message.variant_name();
+                quote! {
+                    #messages_enum_name::#variant(message) => Err(match self.states.active_state_id() {
+                        Some(active_state) => MessageError::not_accepted(#messages_enum_name::#variant(message), active_state),
+                        None => MessageError::NotStarted(#messages_enum_name::#variant(message)),
+                    }),
+                }
+            }).collect();
+        let polls: Vec<TokenStream> = poll_messages.iter().map(|(message, states)| {
+            let variant = message.variant_name();
+            let message_name = &message.name;
+            let message_args = &message.generics;
+            if self.messages.broadcast(message, false).is_some() {
+                return quote! {
+                    if let Ok(Some(message)) = PollAnyMessage::<#message_name#message_args>::poll_any_message(self) {
+                        return Some(#messages_enum_name::#variant(message));
+                    }
+                };
+            }
+            quote! {
+                #(
+                    if let Ok(Some(message)) = PollMessage::<#states, #message_name#message_args>::poll_message(self) {
+                        return Some(#messages_enum_name::#variant(message));
+                    }
+                )*
+            }
+        }).collect();
+
+        let codec = CodecToTokens::new(self.messages);
+        let attributes = &self.messages.attributes;
+
+        tokens.extend(quote! {
+            /// All messages that can be pushed into or polled from the state machine
+            #( #attributes )*
+            #vis enum #messages_enum_name {
+                #( #variants(#types), )*
+            }
+
+            #(
+                impl From<#types> for #messages_enum_name {
+                    fn from(message: #types) -> Self {
+                        #messages_enum_name::#variants(message)
+                    }
+                }
+            )*
+
+            impl DeliverMessage<#messages_enum_name> for #sfsm_name {
+                fn deliver_message(&mut self, message: #messages_enum_name) -> Result<(), MessageError<#messages_enum_name>> {
+                    match message {
+                        #( #deliveries )*
+                        #( #rejections )*
+                    }
+                }
+
+                #[allow(unreachable_patterns)]
+                fn receives(message: &#messages_enum_name) -> bool {
+                    match message {
+                        #( #receivers )*
+                        _ => false,
+                    }
+                }
+            }
+
+            impl DispatchMessage<#messages_enum_name> for #sfsm_name {
+                fn push(&mut self, message: #messages_enum_name) -> Result<(), MessageError<#messages_enum_name>> {
+                    DeliverMessage::<#messages_enum_name>::deliver_message(self, message)
+                }
+
+                fn poll_any(&mut self) -> Option<#messages_enum_name> {
+                    #( #polls )*
+                    None
+                }
+            }
+
+            #codec
+        });
+    }
+}
+
+pub struct CodecToTokens<'a> {
+    messages: &'a Messages,
+}
+
+impl<'a> CodecToTokens<'a> {
+    pub fn new(messages: &'a Messages) -> Self {
+        Self {
+            messages,
+        }
+    }
+}
+
+/// Decodes the pushed messages of the message enum from frames and encodes the polled messages
+/// into frames. The messages are tagged with their codec tags. Only generated with #[sfsm(codec)].
+impl ToTokens for CodecToTokens<'_> {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        if !self.messages.options.codec {
+            return;
+        }
+        let messages_enum_name = &self.messages.messages_enum_name;
+        let codec_tags = self.messages.codec_tags().expect("Internal error. The tags are checked when the messages are parsed");
+        let tag = |message: &Message| codec_tags.iter().find(|(known, _)| known.get_name_type() == message.get_name_type())
+            .map(|(_, tag)| *tag).expect("Internal error. Expected to find the tag of the message");
+        let push_messages = self.messages.push_messages();
+        let poll_messages = self.messages.poll_messages();
+        let push_ids: Vec<u8> = push_messages.iter().map(|(message, _)| tag(message)).collect();
+        let push_variants: Vec<proc_macro2::Ident> = push_messages.iter().map(|(message, _)| message.variant_name()).collect();
+        let poll_ids: Vec<u8> = poll_messages.iter().map(|(message, _)| tag(message)).collect();
+        let poll_variants: Vec<proc_macro2::Ident> = poll_messages.iter().map(|(message, _)| message.variant_name()).collect();
+
+        tokens.extend(quote! {
+            impl MessageCodec for #messages_enum_name {
+                fn decode(frame: &[u8]) -> Result<Self, CodecError> {
+                    let (tag, payload) = decode_frame_tag(frame)?;
+                    match tag {
+                        #( #push_ids => Ok(#messages_enum_name::#push_variants(decode_frame_payload(payload)?)), )*
+                        tag => Err(CodecError::UnknownTag(tag)),
+                    }
+                }
+
+                fn encode<'b>(&self, buffer: &'b mut [u8]) -> Result<&'b mut [u8], CodecError> {
+                    #[allow(unreachable_patterns)]
+                    match self {
+                        #( #messages_enum_name::#poll_variants(message) => encode_frame(#poll_ids, message, buffer), )*
+                        _ => Err(CodecError::NotEncodable),
+                    }
+                }
+            }
         });
+    }
+}
+
+pub struct BroadcastToTokens<'a> {
+    broadcast: &'a Broadcast,
+    messages: &'a Messages,
+}
+
+impl<'a> BroadcastToTokens<'a> {
+    pub fn new(broadcast: &'a Broadcast, messages: &'a Messages) -> Self {
+        Self {
+            broadcast,
+            messages,
+        }
+    }
+
+    /// Pushes the message into the active state. If the message is broadcast to all states, the
+    /// states that do not receive it are skipped with the help of ``` MaybeMessage ```.
+    fn push_into(&self, state: &State, message: &Message) -> TokenStream {
+        let message_name = &message.name;
+        let message_args = &message.generics;
+        if !self.broadcast.all {
+            return quote! {
+                if IsState::<#state>::is_state(self) {
+                    return PushMessage::<#state, #message_name#message_args>::push_message(self, message);
+                }
+            };
+        }
+        let enum_name = &self.messages.enum_name;
+        let enum_entry = &state.enum_name;
+        let trace_push = trace::message(TracePoint::message(self.messages, TraceKind::Push, message, "to", state,
+                                                             quote! { Some(&message) }, self.broadcast.muted));
+        let record_push = record_message(self.messages, quote! {Push}, message, state);
+        quote! {
+            if let #enum_name::#enum_entry(Some(ref mut state)) = self.states {
+                if MaybeMessage::<_, #message_name#message_args>::new(state).receives() {
+                    #trace_push
+                    #record_push
+                }
+                return MaybeMessage::<_, #message_name#message_args>::new(state).try_receive(message)
+                    .map_err(|message| MessageError::not_accepted(message, #enum_name::#enum_entry(None).state_id()));
+            }
+        }
+    }
+
+    /// Checks whether the active state receives the message. If the message is broadcast to all
+    /// states, the states that do not receive it are skipped with the help of ``` MaybeMessage ```.
+    fn receives(&self, state: &State, message: &Message) -> TokenStream {
+        if !self.broadcast.all {
+            return quote! {
+                if IsState::<#state>::is_state(self) {
+                    return true;
+                }
+            };
+        }
+        let message_name = &message.name;
+        let message_args = &message.generics;
+        let enum_name = &self.messages.enum_name;
+        let enum_entry = &state.enum_name;
+        quote! {
+            if let #enum_name::#enum_entry(Some(ref mut state)) = self.states {
+                return MaybeMessage::<_, #message_name#message_args>::new(state).receives();
+            }
+        }
+    }
+
+    /// Polls the message from the active state. If the message is broadcast to all states, the
+    /// states that do not return it are skipped with the help of ``` MaybeMessage ```.
+    fn poll_from(&self, state: &State, message: &Message) -> TokenStream {
+        let message_name = &message.name;
+        let message_args = &message.generics;
+        if !self.broadcast.all {
+            return quote! {
+                if IsState::<#state>::is_state(self) {
+                    return PollMessage::<#state, #message_name#message_args>::poll_message(self);
+                }
+            };
+        }
+        let enum_name = &self.messages.enum_name;
+        let enum_entry = &state.enum_name;
+        let trace_poll = trace::message(TracePoint::message(self.messages, TraceKind::Poll, message, "from", state,
+                                                             quote! { message.as_ref() }, self.broadcast.muted));
+        let record_poll = record_message(self.messages, quote! {Poll}, message, state);
+        quote! {
+            if let #enum_name::#enum_entry(Some(ref mut state)) = self.states {
+                return match MaybeMessage::<_, #message_name#message_args>::new(state).try_return() {
+                    Some(message) => {
+                        if message.is_some() {
+                            #trace_poll
+                            #record_poll
+                        }
+                        Ok(message)
+                    }
+                    None => Err(MessageError::not_accepted((), #enum_name::#enum_entry(None).state_id())),
+                };
+            }
+        }
+    }
+}
+
+impl ToTokens for BroadcastToTokens<'_> {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let sfsm_name = &self.messages.name;
+        let trace_enter = trace::enter(&self.messages.trace_name());
+        let states = &self.broadcast.states;
+        let impl_generics = self.broadcast.message.impl_generics();
+
+        let token_steam = match &self.broadcast.message {
+            MessageDir::Push(message) => {
+                let message_name = &message.name;
+                let message_args = &message.generics;
+                let pushes: Vec<TokenStream> = states.iter().map(|state| self.push_into(state, message)).collect();
+                let receives: Vec<TokenStream> = states.iter().map(|state| self.receives(state, message)).collect();
+                quote! {
+                    impl #impl_generics PushAnyMessage<#message_name#message_args> for #sfsm_name {
+                        fn push_any_message(&mut self, message: #message_name#message_args) -> Result<(), MessageError<#message_name#message_args>> {
+                            #trace_enter
+                            #(#pushes)*
+                            Err(MessageError::inactive(message, self.states.active_state_id()))
+                        }
+
+                        fn receives_any_message(&mut self) -> bool {
+                            // The initial state is set, but not active, before the state machine has been started
+                            if self.states.active_state_id().is_none() {
+                                return false;
+                            }
+                            #(#receives)*
+                            false
+                        }
+                    }
+                }
+            }
+            MessageDir::Poll(message) => {
+                let message_name = &message.name;
+                let message_args = &message.generics;
+                let polls: Vec<TokenStream> = states.iter().map(|state| self.poll_from(state, message)).collect();
+                quote! {
+                    impl #impl_generics PollAnyMessage<#message_name#message_args> for #sfsm_name {
+                        fn poll_any_message(&mut self) -> Result<Option<#message_name#message_args>, MessageError<()>> {
+                            #trace_enter
+                            #(#polls)*
+                            Err(MessageError::inactive((), self.states.active_state_id()))
+                        }
+                    }
+                }
+            }
+            MessageDir::Request(_, _) => unreachable!("Internal error. Requests can not be broadcast"),
+        };
 
         tokens.extend(token_steam);
     }
 }
+
+pub struct RoutesToTokens<'a> {
+    routes: &'a Routes,
+}
+
+impl<'a> RoutesToTokens<'a> {
+    pub fn new(routes: &'a Routes) -> Self {
+        Self {
+            routes,
+        }
+    }
+
+    /// Polls the message from the source and pushes it into the target. The message is only polled
+    /// if the target state is active and receives it, so that it stays in the source otherwise.
+    /// The push into an active state is not rejected, as only the pushes into inactive states are
+    /// queued or deferred. If the source has no message to return, nothing is pushed.
+    fn pump(index: usize, route: &Route) -> TokenStream {
+        let source = route.source.param_name();
+        let source_message = &route.source.message;
+        let source_message_name = &source_message.name;
+        let source_message_args = &source_message.generics;
+        let poll = match &route.source.targets {
+            MessageTargets::State(state) => quote! {
+                PollMessage::<#state, #source_message_name#source_message_args>::poll_message(#source)
+            },
+            _ => quote! {
+                PollAnyMessage::<#source_message_name#source_message_args>::poll_any_message(#source)
+            },
+        };
+
+        let target = route.target.param_name();
+        let target_message = &route.target.message;
+        let target_message_name = &target_message.name;
+        let target_message_args = &target_message.generics;
+        // Convert the message only if the target receives another type
+        let message = if source_message.get_name_type() == target_message.get_name_type() {
+            quote! { message }
+        } else {
+            quote! { message.into() }
+        };
+        let (receives, push) = match &route.target.targets {
+            MessageTargets::State(state) => (quote! {
+                #target.peek_state().active_state_id().is_some() && IsState::<#state>::is_state(#target)
+            }, quote! {
+                PushMessage::<#state, #target_message_name#target_message_args>::push_message(#target, #message)
+            }),
+            _ => (quote! {
+                PushAnyMessage::<#target_message_name#target_message_args>::receives_any_message(#target)
+            }, quote! {
+                PushAnyMessage::<#target_message_name#target_message_args>::push_any_message(#target, #message)
+            }),
+        };
+
+        quote! {
+            if #receives {
+                if let Ok(Some(message)) = #poll {
+                    let pushed = #push;
+                    debug_assert!(pushed.is_ok(), "The message of route {} was rejected by the active state", #index);
+                }
+            }
+        }
+    }
+}
+
+/// Generates the function that pumps the messages along the routes. The state machines are passed
+/// to it in the order they first appear in the routes.
+impl ToTokens for RoutesToTokens<'_> {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let vis = &self.routes.visibility;
+        let name = &self.routes.name;
+        let machines = self.routes.machines();
+        let params: Vec<proc_macro2::Ident> = machines.iter().map(|end| end.param_name()).collect();
+        let machine_types: Vec<&proc_macro2::Ident> = machines.iter().map(|end| &end.machine).collect();
+        let pumps: Vec<TokenStream> = self.routes.routes.iter().enumerate().map(|(index, route)| {
+            RoutesToTokens::pump(index, route)
+        }).collect();
+
+        tokens.extend(quote! {
+            /// Moves the messages polled from the source state machines into the target state
+            /// machines. A message is only polled once the target state is active and receives it.
+            #vis fn #name(#( #params: &mut #machine_types ),*) {
+                #( #pumps )*
+            }
+        });
+    }
+}
@@ -3,12 +3,12 @@
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::ItemFn;
-use crate::generators::{StateMachineToTokens, MessagesToTokens};
+use crate::generators::{StateMachineToTokens, MessagesToTokens, RoutesToTokens};
 mod generators;
 mod parsers;
 mod types;
 mod trace;
-use crate::types::{MatchStateEntry, Machine, TryMachine, Messages, State, DeriveTransition, DeriveTransitionBase};
+use crate::types::{MatchStateEntry, Machine, TryMachine, Messages, Routes, State, DeriveTransition, DeriveTransitionBase};
 
 /// Generates a state machine from a given state machine definition.
 ///
@@ -26,6 +26,10 @@ use crate::types::{MatchStateEntry, Machine, TryMachine, Messages, State, Derive
 /// - [State1, State2, StateN, ...]: Specifies all state structs that will be known to the state machine. Each state must implement the ``` State ``` trait.
 /// - [StateN => StateN, ...]: Defines all transitions between states that can occur. For each transition, the state must implement the according ``` Transition ``` trait.
 ///
+/// Optionally, the messages can be declared as an additional list like ``` [Message1 -> State1, ...] ``` instead of with ``` add_messages! ```.
+/// The states of the messages are then checked against the states of the state machine. The message enum is generated if
+/// the state machine is marked with ``` #[sfsm(dispatch)] ``` or has a message queue or deferred messages.
+///
 /// An example might look like this:
 /// ```rust
 /// # use sfsm_proc::add_state_machine;
@@ -108,6 +112,8 @@ pub fn add_state_machine(input: TokenStream) -> TokenStream {
 /// - ErrorType: Defines the type of error that can be returned from the states.
 /// - ErrorState: Defines the state that will act as the error handle state. It must implement the ``` TryErrorState ``` trait. Adding it to the state definitions is optional.
 ///
+/// As with ``` add_state_machine! ```, the messages can be declared as an additional list after the error state.
+///
 /// ```rust
 /// # use sfsm_base::fallible::*;
 /// # use sfsm_proc::add_fallible_state_machine;
@@ -213,6 +219,35 @@ pub fn add_fallible_state_machine(input: TokenStream) -> TokenStream {
 /// - StateMachineName: This must match a previously with add_state_machine defined state machine.
 /// - [ Message1 <- State1, ... ] Defines all messages that can be passed back an forth. The message specifies the struct/enum that will be used as a message, the <- arrow defines a poll and the -> a push and the state is the target or source state.
 /// For each message, the source/target state must implement the according ``` ReceiveMessage ``` or ``` ReturnMessage ``` trait.
+///
+/// The states must be states of the state machine, otherwise an error is reported at the state. To broadcast a message to all states
+/// with ``` * ```, the state machine is asked for its states, so it must be imported where the messages are declared.
+///
+/// Messages that borrow their data are declared with a named lifetime like ``` Frame<'a> -> State1 ```. The generated
+/// implementations are generic over the lifetime. Borrowed messages can not be deferred and are left out of the message enum.
+///
+/// A request that the state answers right away is defined with ``` Request => Reply @ State ```. The state must implement the
+/// ``` ReplyMessage ``` trait and the request is sent with the ``` RequestMessage ``` trait.
+///
+/// Instead of a single state, a list of states like ``` Message -> [State1, State2] ``` or all states with ``` Message -> * ```
+/// can be given. The message is then pushed into or polled from whichever of the states is active with the ``` PushAnyMessage ```
+/// and ``` PollAnyMessage ``` traits. The states that do not implement the message traits are skipped for ``` * ```.
+///
+/// A pushed message marked with ``` #[sfsm(defer)] ``` is held until its target state is active instead of being
+/// returned. The state machine must then be defined with ``` #[sfsm(defer = N)] ```, which sets how many messages it holds.
+///
+/// In a fallible state machine, a pushed message marked with ``` #[sfsm(fallible)] ``` is received with the ``` TryReceiveMessage ```
+/// trait and pushed with the ``` TryPushMessage ``` trait. If the state returns an error, the state machine enters its error state.
+///
+/// With the ``` #[sfsm(dispatch)] ``` attribute, the macro also generates the ``` StateMachineNameMessages ``` enum with a
+/// variant for each declared message type. It is private unless a visibility is put in front of the name, like
+/// ``` pub Rocket ```. All other attributes, like ``` #[derive(Debug)] ```, are added to the enum. With the
+/// ``` DispatchMessage ``` trait, the messages of the enum are pushed into and polled from whichever state is active.
+/// State machines with a message queue or deferred messages hold the messages in the enum, so their messages must be
+/// declared with the attribute. Only one ``` add_messages! ``` macro per state machine can generate the enum.
+/// With the ``` #[sfsm(codec)] ``` attribute, which implies ``` #[sfsm(dispatch)] ```, the enum also implements
+/// ``` MessageCodec ``` to decode the pushed messages from frames and to encode the polled messages into frames. This
+/// requires the codec feature. The tag of a message in the frames can be pinned with ``` #[sfsm(tag = N)] ```.
 /// An example might look like this.
 /// ```rust
 /// # use sfsm_proc::add_state_machine;
@@ -280,7 +315,17 @@ pub fn add_fallible_state_machine(input: TokenStream) -> TokenStream {
 #[proc_macro]
 pub fn add_messages(input: TokenStream) -> TokenStream {
 
+    let definitions = proc_macro2::TokenStream::from(input.clone());
     let definition = syn::parse_macro_input!(input as Messages);
+    if definition.requires_states {
+        // Request the states from the state machine and come back with them. Without the path of
+        // the sfsm crate, add_messages! must be in scope.
+        let states_macro_name = Machine::states_macro_name(&definition.name);
+        let add_messages = definition.callback.clone().unwrap_or_else(|| quote! { add_messages });
+        return TokenStream::from(quote! {
+            #states_macro_name! { #add_messages; #definitions }
+        });
+    }
     let messages_to_tokens = MessagesToTokens::new(&definition);
 
     TokenStream::from(quote!{
@@ -288,6 +333,76 @@ pub fn add_messages(input: TokenStream) -> TokenStream {
     })
 }
 
+/// Generates a function that moves the messages polled from one state machine into another one.
+///
+/// The route definition is expected too hold to the following pattern:
+/// ```rust,ignore
+/// add_routes!(
+///     pump_name,
+///     [
+///         SourceMachine: Message1 <- State1 => TargetMachine: Message1 -> State2,
+///         ...
+///     ]
+/// );
+/// ```
+/// - pump_name: The name of the generated function. A visibility can be put in front of it.
+/// - [ SourceMachine: Message1 <- State1 => TargetMachine: Message1 -> State2, ... ] Defines the routes. The message is polled from the state of the source state machine and pushed into the state of the target state machine.
+///
+/// The messages must be declared with add_messages! for both state machines.
+///
+/// Instead of a single state, a list of states or ``` * ``` can be given if the message is broadcast accordingly. If the target
+/// receives another message type, the polled message is converted with ``` Into ```.
+///
+/// The function takes the state machines in the order they first appear in the routes, like
+/// ``` fn pump_name(source_machine: &mut SourceMachine, target_machine: &mut TargetMachine) ```.
+/// Each call moves at most one message per route. A message is only polled if the target state is active and receives it, so it
+/// stays in the source while the target is in another state. Once polled, it is always delivered.
+/// ```rust
+/// # use sfsm_proc::{add_state_machine, add_messages, add_routes};
+/// # use sfsm_base::*;
+/// # struct Measuring {}
+/// # struct Regulating { reading: Option<u16> }
+/// # impl State for Measuring {}
+/// # impl State for Regulating {}
+/// # #[derive(Debug)]
+/// # struct Reading { value: u16 }
+/// # impl ReturnMessage<Reading> for Measuring {
+/// #     fn return_message(&mut self) -> Option<Reading> {
+/// #         Some(Reading { value: 42 })
+/// #     }
+/// # }
+/// # impl ReceiveMessage<Reading> for Regulating {
+/// #     fn receive_message(&mut self, message: Reading) {
+/// #         self.reading = Some(message.value);
+/// #     }
+/// # }
+/// # add_state_machine!(Sensor, Measuring, [Measuring], []);
+/// # add_state_machine!(Controller, Regulating, [Regulating], []);
+/// # add_messages!(Sensor, [Reading <- Measuring]);
+/// # add_messages!(Controller, [Reading -> Regulating]);
+/// add_routes!(
+///     pump,
+///     [
+///         Sensor: Reading <- Measuring => Controller: Reading -> Regulating,
+///     ]
+/// );
+///
+/// # let mut sensor = Sensor::new();
+/// # sensor.start(Measuring {}).unwrap();
+/// # let mut controller = Controller::new();
+/// # controller.start(Regulating { reading: None }).unwrap();
+/// pump(&mut sensor, &mut controller);
+///```
+#[proc_macro]
+pub fn add_routes(input: TokenStream) -> TokenStream {
+    let definition = syn::parse_macro_input!(input as Routes);
+    let routes_to_tokens = RoutesToTokens::new(&definition);
+
+    TokenStream::from(quote!{
+        #routes_to_tokens
+    })
+}
+
 /// Generate the enum entry of a state. Expects the name of the sfsm and the name (and type args)
 /// of the state as well as the desired name of the variable to work with as arguments.
 /// Can be used to generate match branches for example.
@@ -336,6 +451,71 @@ pub fn sfsm_trace(_attr: TokenStream, item: TokenStream) -> TokenStream {
     })
 }
 
+/// Creates a wrapper around a function the records of the state machines are passed to.
+/// With the help of ``` sfsm_record ``` and the trace-record feature, a function can be
+/// configured that receives a ``` TraceRecord ``` for every transition, error state entry and message.
+/// ```rust,ignore
+/// #[sfsm_record]
+/// fn record(record: &TraceRecord) {
+///     RECORDER.lock().unwrap().record(record);
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn sfsm_record(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let record_function: ItemFn = syn::parse_macro_input!(item as ItemFn);
+    let record_function_ident: &proc_macro2::Ident = &record_function.sig.ident;
+    TokenStream::from(quote!{
+        #record_function
+        fn __sfsm_record(trace_record: &TraceRecord) {
+            #record_function_ident(trace_record);
+        }
+    })
+}
+
+/// Creates a wrapper around a function the binary traces are forwarded to.
+/// With the help of ``` sfsm_trace_binary ``` and the trace-binary feature, a function can be
+/// configured that receives a frame of a few bytes for every trace point. The frames can be turned
+/// back into logs on the host with the dictionaries of the state machines.
+/// ```rust,ignore
+/// #[sfsm_trace_binary]
+/// fn trace(frame: &[u8]) {
+///     uart.write(frame);
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn sfsm_trace_binary(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let trace_function: ItemFn = syn::parse_macro_input!(item as ItemFn);
+    let trace_function_ident: &proc_macro2::Ident = &trace_function.sig.ident;
+    TokenStream::from(quote!{
+        #trace_function
+        fn __sfsm_trace_binary(frame: &[u8]) {
+            #trace_function_ident(frame);
+        }
+    })
+}
+
+/// Creates a wrapper around the clock that timestamps the traces.
+/// With the help of ``` sfsm_clock ``` and the trace-time feature, a function can be configured that
+/// returns the current time in any unit, for example the ticks of a timer. Every trace point and
+/// record then contains the time and the number of steps the state machine has executed.
+/// ```rust,ignore
+/// #[sfsm_clock]
+/// fn clock() -> u64 {
+///     timer.ticks()
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn sfsm_clock(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let clock_function: ItemFn = syn::parse_macro_input!(item as ItemFn);
+    let clock_function_ident: &proc_macro2::Ident = &clock_function.sig.ident;
+    TokenStream::from(quote!{
+        #clock_function
+        fn __sfsm_clock() -> u64 {
+            #clock_function_ident()
+        }
+    })
+}
+
 /// Derives an empty transition of a transition from one state into another and allows to
 /// customise if it should always transit or never.
 /// ```rust,ignore
@@ -1,11 +1,12 @@
 use proc_macro2::{Ident, Span};
 use proc_macro::{TokenStream};
-use syn::{Result, AngleBracketedGenericArguments, Visibility, Attribute, Error, TypePath};
+use syn::{Result, AngleBracketedGenericArguments, Visibility, Attribute, Error, TypePath, Lit, LitStr, Meta};
 use syn::parse::{Parse, ParseStream, Parser};
 use syn::punctuated::{Punctuated};
 use syn::Token;
 use quote::{quote};
-use crate::types::{State, Transition, Machine, StateEntry, MatchStateEntry, StateMessage, Messages, Message, MessageDir, ErrorType, TryMachine, Mode, TraitDefinitions, DeriveTransitionBase, DeriveTransition};
+use crate::types::{State, Transition, Machine, StateEntry, MatchStateEntry, StateMessage, Messages, Message, MessageDir, MessageDefinition, MessageTargets, Broadcast, Route, RouteEnd, Routes, ErrorType, TryMachine, Mode, TraitDefinitions, DeriveTransitionBase, DeriveTransition, Options};
+use sfsm_base::trace::TraceMask;
 
 /// Parses the name of a state and optionally a type.
 /// For example Foo or Bar<T>
@@ -33,8 +34,10 @@ impl Parse for State {
 
 /// Parses a transition that must be in the form of
 /// Foo -> Bar or optionally with types like Foo<T> -> Bar<T>
+/// The transition can be muted with #[sfsm(mute)] in front of it.
 impl Parse for Transition {
     fn parse(input: ParseStream) -> Result<Self> {
+        let muted = Options::parse_mute(input)?;
         let src: State = input.parse()?;
         input.parse::<syn::Token![=]>()?;
         input.parse::<syn::Token![>]>()?;
@@ -42,11 +45,104 @@ impl Parse for Transition {
 
         Ok(Self {
             src,
-            dst
+            dst,
+            muted
         })
     }
 }
 
+impl Options {
+    /// Takes the options out of the #[sfsm(...)] attributes and returns the remaining attributes.
+    /// Only the allowed options can be set.
+    pub fn from_attributes(attributes: Vec<Attribute>, allowed: &[&str]) -> Result<(Self, Vec<Attribute>)> {
+        let mut options = Options::default();
+        let mut remaining = vec![];
+        for attribute in attributes {
+            if !attribute.path.is_ident("sfsm") {
+                remaining.push(attribute);
+                continue;
+            }
+            let metas = attribute.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+            for meta in metas {
+                if !allowed.iter().any(|option| meta.path().is_ident(option)) {
+                    return Err(Error::new_spanned(meta.path(), format!("Unsupported sfsm option. Expected {}", allowed.join(" or "))));
+                }
+                match meta {
+                    Meta::NameValue(value) if value.path.is_ident("parent") => match value.lit {
+                        Lit::Str(parent) => options.parent = Some(Options::parse_parent(parent)?),
+                        lit => return Err(Error::new_spanned(lit, "Expected the path of the parent state machine as a string like \"ForwardObserver\"")),
+                    },
+                    Meta::NameValue(value) if value.path.is_ident("trace") => match value.lit {
+                        Lit::Str(trace) => options.trace = Options::parse_trace(&trace)?,
+                        lit => return Err(Error::new_spanned(lit, "Expected the traced categories as a string like \"transitions, messages\"")),
+                    },
+                    Meta::Path(path) if path.is_ident("mute") => options.mute = true,
+                    Meta::NameValue(value) if value.path.is_ident("queue") => match value.lit {
+                        Lit::Int(queue) => options.queue = Some(queue.base10_parse()?),
+                        lit => return Err(Error::new_spanned(lit, "Expected the capacity of the message queue as a number like 8")),
+                    },
+                    Meta::NameValue(value) if value.path.is_ident("defer") => match value.lit {
+                        Lit::Int(defer) => options.defer = Some(defer.base10_parse()?),
+                        lit => return Err(Error::new_spanned(lit, "Expected the number of deferred messages as a number like 4")),
+                    },
+                    Meta::Path(path) if path.is_ident("defer") => options.deferred = true,
+                    Meta::Path(path) if path.is_ident("fallible") => options.fallible = true,
+                    Meta::Path(path) if path.is_ident("dispatch") => options.dispatch = true,
+                    Meta::Path(path) if path.is_ident("codec") => options.codec = true,
+                    Meta::NameValue(value) if value.path.is_ident("tag") => match value.lit {
+                        Lit::Int(tag) => options.tag = Some(tag.base10_parse()?),
+                        lit => return Err(Error::new_spanned(lit, "Expected the tag of the message as a number like 3")),
+                    },
+                    meta => return Err(Error::new_spanned(meta, "Invalid sfsm option. Expected parent = \"...\", trace = \"...\", queue = ..., defer = ..., tag = ..., mute, defer, fallible, dispatch or codec")),
+                }
+            }
+        }
+        Ok((options, remaining))
+    }
+
+    /// Checks that the path of the parent consists of the names of state machines separated by a
+    /// slash, like "ForwardObserver/Online".
+    fn parse_parent(parent: LitStr) -> Result<LitStr> {
+        if parent.value().split('/').any(|name| syn::parse_str::<Ident>(name).is_err()) {
+            return Err(Error::new_spanned(parent, "Expected the path of the parent state machine like \"ForwardObserver\" or \"ForwardObserver/Online\""));
+        }
+        Ok(parent)
+    }
+
+    /// Parses the comma separated categories of trace points, like "transitions, messages".
+    fn parse_trace(trace: &LitStr) -> Result<TraceMask> {
+        let mut mask = TraceMask::NONE;
+        for category in trace.value().split(',').map(|category| category.trim()) {
+            mask |= match category {
+                "transitions" => TraceMask::TRANSITIONS,
+                "steps" => TraceMask::STEPS,
+                "messages" => TraceMask::MESSAGES,
+                "guards" => TraceMask::GUARDS,
+                "all" => TraceMask::ALL,
+                "none" => TraceMask::NONE,
+                _ => return Err(Error::new_spanned(trace, format!("Unknown trace category '{}'. Expected transitions, steps, messages, guards, all or none", category))),
+            };
+        }
+        Ok(mask)
+    }
+
+    /// Parses the #[sfsm(mute)] marker in front of a state or transition.
+    fn parse_mute(input: ParseStream) -> Result<bool> {
+        Ok(Options::parse_markers(input, &["mute"])?.mute)
+    }
+
+    /// Parses the #[sfsm(...)] markers in front of a state, transition or message.
+    fn parse_markers(input: ParseStream, allowed: &[&str]) -> Result<Self> {
+        let attributes = input.call(Attribute::parse_outer)?;
+        let (options, attributes) = Options::from_attributes(attributes, allowed)?;
+        if let Some(attribute) = attributes.first() {
+            let markers: Vec<String> = allowed.iter().map(|marker| format!("#[sfsm({})]", marker)).collect();
+            return Err(Error::new_spanned(attribute, format!("Only the {} attribute can be added here", markers.join(" or "))));
+        }
+        Ok(options)
+    }
+}
+
 impl Machine {
     pub fn enum_name(sfsm_name: &Ident) -> Ident {
         Ident::new(format!("{}States", sfsm_name.to_string()).as_str(),
@@ -56,10 +152,12 @@ impl Machine {
 
 /// Parses the state machine in the form of
 /// name, Foo, [Foo, Bar], [Foo -> Bar]
+/// optionally followed by the messages, like [M1 -> Foo]
 impl Parse for Machine {
     fn parse(input: ParseStream) -> Result<Self> {
 
         let attributes = input.call(Attribute::parse_outer)?;
+        let (options, attributes) = Options::from_attributes(attributes, &["parent", "trace", "queue", "defer", "dispatch", "codec"])?;
 
         let visibility: Option<Visibility> = input.parse().ok();
 
@@ -71,9 +169,15 @@ impl Parse for Machine {
 
         let state_group = input.parse::<proc_macro2::Group>()?;
         let state_group_ts: TokenStream = state_group.stream().into();
-        let state_parser = Punctuated::<State, Token![,]>::parse_terminated;
+        let state_parser = |input: ParseStream| {
+            Punctuated::<(bool, State), Token![,]>::parse_terminated_with(input, |input| {
+                Ok((Options::parse_mute(input)?, input.parse::<State>()?))
+            })
+        };
         let punctuated_state_names = state_parser.parse(state_group_ts)?;
-        let states_names: Vec<State> = punctuated_state_names.into_iter().collect();
+        let muted_states: Vec<Ident> = punctuated_state_names.iter().filter(|(muted, _)| *muted)
+            .map(|(_, state)| state.enum_name.clone()).collect();
+        let states_names: Vec<State> = punctuated_state_names.into_iter().map(|(_, state)| state).collect();
 
         input.parse::<syn::Token![,]>()?;
 
@@ -83,6 +187,8 @@ impl Parse for Machine {
             Punctuated::<Transition, Token![,]>::parse_terminated;
         let punctuated_transitions = transition_parser.parse(transition_group_ts)?;
         let transitions: Vec<Transition> = punctuated_transitions.into_iter().collect();
+        let muted_transitions: Vec<(Ident, Ident)> = transitions.iter().filter(|trans| trans.muted)
+            .map(|trans| (trans.src.enum_name.clone(), trans.dst.enum_name.clone())).collect();
 
         let states: Vec<State> = states_names.into_iter().map(|state| {
 
@@ -131,7 +237,10 @@ impl Parse for Machine {
         };
 
 
-        Ok(Self {
+        let mut machine = Self {
+            options,
+            muted_states,
+            muted_transitions,
             attributes,
             visibility,
             name,
@@ -143,7 +252,10 @@ impl Parse for Machine {
             mode: Mode::NonFallible,
             error_state: None,
             custom_error: None,
-        })
+            messages: None,
+        };
+        machine.messages = Messages::parse_optional(input, &machine)?;
+        Ok(machine)
     }
 }
 
@@ -191,8 +303,12 @@ impl Parse for Message {
     }
 }
 
-impl Parse for StateMessage {
+/// Parses a message definition in the form of M1 -> Foo or M2 <- Bar. Instead of a single state,
+/// the message can be broadcast to a list of states like M1 -> [Foo, Bar] or to all states with
+/// M1 -> *. Requests that are answered with a reply are written as M1 => M2 @ Foo.
+impl Parse for MessageDefinition {
     fn parse(input: ParseStream) -> Result<Self> {
+        let markers = Options::parse_markers(input, &["mute", "defer", "fallible", "tag"])?;
         let message: Message = input.parse()?;
 
         let message_dir: MessageDir = if input.peek(syn::Token![->]) {
@@ -201,14 +317,61 @@ impl Parse for StateMessage {
         } else if input.peek(syn::Token![<-]) {
             input.parse::<syn::Token![<-]>()?;
             MessageDir::Poll(message)
+        } else if input.peek(syn::Token![=>]) {
+            input.parse::<syn::Token![=>]>()?;
+            let reply: Message = input.parse()?;
+            input.parse::<syn::Token![@]>()?;
+            if input.peek(Token![*]) || input.peek(syn::token::Bracket) {
+                return Err(Error::new(input.span(), "A request can only be sent to a single state"));
+            }
+            MessageDir::Request(message, reply)
         } else {
-            return Err(Error::new(input.span(), format!("A direction must be specified with either '->' or '<-' but got '{}' instead", input)))
+            return Err(Error::new(input.span(), format!("A direction must be specified with either '->', '<-' or '=>' but got '{}' instead", input)))
         };
 
-        let state: State = input.parse()?;
+        if markers.deferred || markers.fallible {
+            if let MessageDir::Poll(message) | MessageDir::Request(message, _) = &message_dir {
+                return Err(Error::new_spanned(&message.name, "Only pushed messages can be deferred or fallible"));
+            }
+        }
+        for message in message_dir.messages() {
+            if let Some(lifetime) = message.all_lifetimes().iter().find(|lifetime| lifetime.ident == "_") {
+                return Err(Error::new(lifetime.span(), "The lifetime of the message must be named, like Frame<'a>"));
+            }
+        }
+        if markers.deferred && message_dir.message().is_borrowed() {
+            return Err(Error::new_spanned(&message_dir.message().name, "Borrowed messages can not be deferred"));
+        }
+        if markers.deferred && markers.fallible {
+            return Err(Error::new_spanned(&message_dir.message().name, "Fallible messages can not be deferred"));
+        }
+
+        let targets = if input.peek(Token![*]) {
+            input.parse::<Token![*]>()?;
+            MessageTargets::All
+        } else if input.peek(syn::token::Bracket) {
+            let states_group = input.parse::<proc_macro2::Group>()?;
+            let states_parser = Punctuated::<State, Token![,]>::parse_terminated;
+            let states: Vec<State> = states_parser.parse(states_group.stream().into())?.into_iter().collect();
+            if states.is_empty() {
+                return Err(Error::new_spanned(states_group, "Expected at least one state to broadcast the message to"));
+            }
+            MessageTargets::States(states)
+        } else {
+            MessageTargets::State(input.parse()?)
+        };
+
+        if (markers.deferred || markers.fallible) && !matches!(targets, MessageTargets::State(_)) {
+            return Err(Error::new_spanned(&message_dir.message().name, "Broadcast messages can not be deferred or fallible"));
+        }
+
         Ok(Self {
             message: message_dir,
-            state
+            targets,
+            muted: markers.mute,
+            deferred: markers.deferred,
+            fallible: markers.fallible,
+            tag: markers.tag,
         })
     }
 }
@@ -216,24 +379,302 @@ impl Parse for StateMessage {
 
 /// Parses the message definitions in the form of
 /// name, [M1 -> Foo, M2 <- Bar]
+/// A visibility can be put in front of the name. The sfsm crate puts the path add_messages! is
+/// called back with in front, like @callback $crate::add_messages; name, [M1 -> Foo]. To broadcast
+/// messages to all states, the definitions are passed back with the states and the parent of the
+/// state machine in front, like @states [Foo, Bar] @parent "Outer" name, [M1 -> *]
 impl Parse for Messages {
     fn parse(input: ParseStream) -> Result<Self> {
 
+        let mut callback: Option<proc_macro2::TokenStream> = None;
+        let mut machine_states: Option<Vec<State>> = None;
+        // The state machine passes its parent along with the states
+        let mut machine_parent: Option<LitStr> = None;
+        while input.peek(Token![@]) {
+            input.parse::<Token![@]>()?;
+            let marker: Ident = input.parse()?;
+            if marker == "callback" {
+                let mut path = proc_macro2::TokenStream::new();
+                while !input.peek(Token![;]) {
+                    path.extend(std::iter::once(input.parse::<proc_macro2::TokenTree>()?));
+                }
+                input.parse::<Token![;]>()?;
+                callback = Some(path);
+            } else if marker == "states" {
+                let states_group = input.parse::<proc_macro2::Group>()?;
+                let states_parser = Punctuated::<State, Token![,]>::parse_terminated;
+                machine_states = Some(states_parser.parse(states_group.stream().into())?.into_iter().collect());
+            } else if marker == "parent" {
+                machine_parent = Some(input.parse()?);
+            } else {
+                return Err(Error::new_spanned(marker, "Unexpected marker"));
+            }
+        }
+
+        let attributes = input.call(Attribute::parse_outer)?;
+        let (mut options, attributes) = Options::from_attributes(attributes, &["parent", "dispatch", "codec"])?;
+        // The codec is implemented for the message enum
+        options.dispatch |= options.codec;
+        if let (false, Some(attribute)) = (options.dispatch, attributes.first()) {
+            return Err(Error::new_spanned(attribute, "The attributes are added to the message enum, which is only generated with #[sfsm(dispatch)]"));
+        }
+
+        let visibility: Option<Visibility> = input.parse().ok();
+
         let name: Ident = input.parse()?;
         input.parse::<syn::Token![,]>()?;
 
+        // The messages are traced with the parent of their state machine. It does not have to be
+        // repeated, but if it is, it must be the same.
+        if machine_states.is_some() {
+            if let Some(parent) = &options.parent {
+                if machine_parent.as_ref().map(LitStr::value) != Some(parent.value()) {
+                    let machine_parent = machine_parent.as_ref().map_or("no parent".to_string(), |parent| format!("the parent \"{}\"", parent.value()));
+                    return Err(Error::new_spanned(parent, format!("{} has {}. The messages take the parent of their state machine, so it can be left out",
+                        name, machine_parent)));
+                }
+            }
+            options.parent = machine_parent;
+        }
+
+        let definitions = MessageDefinition::parse_group(input)?;
+        let mut messages = Messages::from_definitions(options, attributes, visibility, name, definitions, machine_states.as_deref())?;
+        messages.callback = callback;
+        Ok(messages)
+    }
+}
+
+impl MessageDefinition {
+    /// Parses a group of message definitions like [M1 -> Foo, M2 <- Bar]
+    fn parse_group(input: ParseStream) -> Result<Vec<MessageDefinition>> {
         let state_message_group = input.parse::<proc_macro2::Group>()?;
         let state_message_group_ts: TokenStream = state_message_group.stream().into();
-        let state_message_parser = Punctuated::<StateMessage, Token![,]>::parse_terminated;
-        let punctuated_state_names = state_message_parser.parse(state_message_group_ts)?;
-        let messages: Vec<StateMessage> = punctuated_state_names.into_iter().collect();
+        let state_message_parser = Punctuated::<MessageDefinition, Token![,]>::parse_terminated;
+        Ok(state_message_parser.parse(state_message_group_ts)?.into_iter().collect())
+    }
+}
+
+impl Messages {
+    /// Collects the message definitions of a state machine. If the states of the state machine
+    /// are known, the states the messages are passed to and from are checked against them.
+    pub fn from_definitions(options: Options, attributes: Vec<Attribute>, visibility: Option<Visibility>, name: Ident,
+                            definitions: Vec<MessageDefinition>, machine_states: Option<&[State]>) -> Result<Self> {
+        if let Some(machine_states) = machine_states {
+            for definition in &definitions {
+                let states = match &definition.targets {
+                    MessageTargets::State(state) => std::slice::from_ref(state),
+                    MessageTargets::States(states) => states.as_slice(),
+                    MessageTargets::All => &[],
+                };
+                let unknown = states.iter().find(|state| {
+                    !machine_states.iter().any(|known| known.enum_name == state.enum_name)
+                });
+                if let Some(state) = unknown {
+                    let known: Vec<String> = machine_states.iter().map(|known| known.get_name_type()).collect();
+                    return Err(Error::new_spanned(&state.name, format!("{} is not a state of {}. The states are: {}",
+                        state.get_name_type(), name, known.join(", "))));
+                }
+            }
+        }
+
+        let mut messages: Vec<StateMessage> = vec![];
+        let mut broadcasts: Vec<Broadcast> = vec![];
+        let mut tags: Vec<(Message, u8)> = vec![];
+        // Only the messages broadcast to all states need the states of the state machine
+        let requires_states = machine_states.is_none()
+            && definitions.iter().any(|definition| matches!(definition.targets, MessageTargets::All));
+        for definition in definitions {
+            if let Some(tag) = definition.tag {
+                let message = definition.message.message();
+                if !options.codec {
+                    return Err(Error::new_spanned(&message.name, "Tags can only be pinned if the messages are declared with #[sfsm(codec)]"));
+                }
+                let conflict = tags.iter().find(|(known, known_tag)| {
+                    (known.get_name_type() == message.get_name_type()) != (*known_tag == tag)
+                });
+                if let Some((known, _)) = conflict {
+                    return Err(Error::new_spanned(&message.name, format!("The tag {} conflicts with the tag of {}", tag, known.get_name_type())));
+                }
+                tags.push((message.clone(), tag));
+            }
+            let all = matches!(definition.targets, MessageTargets::All);
+            let states = match definition.targets {
+                MessageTargets::State(state) => {
+                    messages.push(StateMessage {
+                        state,
+                        message: definition.message,
+                        muted: definition.muted,
+                        deferred: definition.deferred,
+                        fallible: definition.fallible,
+                    });
+                    continue;
+                }
+                MessageTargets::States(states) => {
+                    for state in &states {
+                        messages.push(StateMessage {
+                            state: state.clone(),
+                            message: definition.message.clone(),
+                            muted: definition.muted,
+                            deferred: false,
+                            fallible: false,
+                        });
+                    }
+                    Some(states)
+                }
+                MessageTargets::All => machine_states.map(|states| states.to_vec()),
+            };
+
+            let message = definition.message.message();
+            let push = matches!(definition.message, MessageDir::Push(_));
+            let duplicate = broadcasts.iter().any(|broadcast| {
+                matches!(broadcast.message.directed(push), Some(known) if known.get_name_type() == message.get_name_type())
+            });
+            if duplicate {
+                return Err(Error::new_spanned(&message.name, "The message can only be broadcast once in each direction"));
+            }
+
+            if let Some(states) = states {
+                broadcasts.push(Broadcast {
+                    all,
+                    message: definition.message,
+                    states,
+                    muted: definition.muted,
+                });
+            }
+        }
 
         let enum_name = Machine::enum_name(&name);
+        let messages_enum_name = Ident::new(&format!("{}Messages", name), Span::call_site());
 
-        Ok(Self {
+        let messages = Self {
+            options,
+            attributes,
+            visibility,
             name,
             enum_name,
-            messages
+            messages_enum_name,
+            messages,
+            broadcasts,
+            tags,
+            requires_states,
+            callback: None,
+        };
+        // The messages are checked once the states are known
+        if messages.requires_states {
+            return Ok(messages);
+        }
+        if messages.options.dispatch {
+            let enum_messages = messages.enum_messages();
+            for (index, message) in enum_messages.iter().enumerate() {
+                let variant = message.variant_name();
+                if let Some(known) = enum_messages[..index].iter().find(|known| known.variant_name() == variant) {
+                    return Err(Error::new_spanned(&message.name, format!("{} and {} would both be the variant {} of {}. Rename one of the messages",
+                        known.get_name_type(), message.get_name_type(), variant, messages.messages_enum_name)));
+                }
+            }
+        }
+        if messages.options.codec {
+            let codec_tags = messages.codec_tags()?;
+            let unencoded = messages.tags.iter().find(|(message, _)| {
+                !codec_tags.iter().any(|(known, _)| known.get_name_type() == message.get_name_type())
+            });
+            if let Some((message, _)) = unencoded {
+                return Err(Error::new_spanned(&message.name, "Only the messages that are encoded by the codec can be tagged. Fallible, borrowed and requested messages are not encoded"));
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Parses the messages that are optionally declared after the definition of a state machine,
+    /// like [Foo, Bar], [Foo => Bar], [M1 -> Foo]
+    fn parse_optional(input: ParseStream, machine: &Machine) -> Result<Option<Self>> {
+        if !(input.peek(Token![,]) && input.peek2(syn::token::Bracket)) {
+            return Ok(None);
+        }
+        input.parse::<syn::Token![,]>()?;
+        let definitions = MessageDefinition::parse_group(input)?;
+        if let Mode::NonFallible = machine.mode {
+            if let Some(definition) = definitions.iter().find(|definition| definition.fallible) {
+                return Err(Error::new_spanned(&definition.message.message().name,
+                    "Only the messages of fallible state machines can be fallible"));
+            }
+        }
+        // The queue and the deferred messages are held in the message enum
+        let options = Options {
+            parent: machine.options.parent.clone(),
+            dispatch: machine.options.dispatch || machine.options.codec || machine.options.queue.is_some() || machine.options.defer.is_some(),
+            codec: machine.options.codec,
+            ..Options::default()
+        };
+        let messages = Messages::from_definitions(options, vec![], machine.visibility.clone(),
+                                                  machine.name.clone(), definitions, Some(&machine.states))?;
+        Ok(Some(messages))
+    }
+}
+
+impl RouteEnd {
+    /// Parses one end of a route in the form of Sensor: M1 <- Foo. The message must be polled from
+    /// the source and pushed into the target.
+    fn parse_directed(input: ParseStream, push: bool) -> Result<Self> {
+        let machine: Ident = input.parse()?;
+        input.parse::<syn::Token![:]>()?;
+        let definition: MessageDefinition = input.parse()?;
+        let message = match (definition.message, push) {
+            (MessageDir::Push(message), true) | (MessageDir::Poll(message), false) => message,
+            (message_dir, _) => {
+                let expected = if push { "pushed into the target with '->'" } else { "polled from the source with '<-'" };
+                return Err(Error::new_spanned(&message_dir.message().name, format!("The message must be {}", expected)));
+            }
+        };
+        if definition.muted || definition.deferred || definition.fallible {
+            return Err(Error::new_spanned(&message.name, "Routes can not be marked with #[sfsm(...)]"));
+        }
+        if message.is_borrowed() {
+            return Err(Error::new_spanned(&message.name, "Borrowed messages can not be routed"));
+        }
+        Ok(Self {
+            machine,
+            message,
+            targets: definition.targets,
+        })
+    }
+}
+
+/// Parses a route in the form of
+/// Sensor: M1 <- Foo => Controller: M1 -> Bar
+impl Parse for Route {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let source = RouteEnd::parse_directed(input, false)?;
+        input.parse::<syn::Token![=>]>()?;
+        let target = RouteEnd::parse_directed(input, true)?;
+        Ok(Self {
+            source,
+            target,
+        })
+    }
+}
+
+/// Parses the routes in the form of
+/// name, [Sensor: M1 <- Foo => Controller: M1 -> Bar]
+/// A visibility can be put in front of the name of the pump function.
+impl Parse for Routes {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let visibility: Option<Visibility> = input.parse().ok();
+        let name: Ident = input.parse()?;
+        input.parse::<syn::Token![,]>()?;
+
+        let route_group = input.parse::<proc_macro2::Group>()?;
+        let route_group_ts: TokenStream = route_group.stream().into();
+        let route_parser = Punctuated::<Route, Token![,]>::parse_terminated;
+        let routes: Vec<Route> = route_parser.parse(route_group_ts)?.into_iter().collect();
+        if routes.is_empty() {
+            return Err(Error::new_spanned(route_group, "Expected at least one route"));
+        }
+
+        Ok(Self {
+            visibility,
+            name,
+            routes,
         })
     }
 }
@@ -258,6 +699,7 @@ impl Parse for ErrorType {
 
 /// Parses the state machine in the form of
 /// name, Foo, [Foo, Bar], [Foo -> Bar], ErrorType, ErrorState
+/// optionally followed by the messages, like [M1 -> Foo]
 impl Parse for TryMachine {
     fn parse(input: ParseStream) -> Result<Self> {
 
@@ -293,6 +735,7 @@ impl Parse for TryMachine {
             action: proc_macro2::TokenStream::from(quote! {try_action}),
             execute: proc_macro2::TokenStream::from(quote! {try_execute}),
         };
+        state_machine.messages = Messages::parse_optional(input, &state_machine)?;
 
         Ok(Self {
             state_machine
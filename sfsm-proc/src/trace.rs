@@ -1,6 +1,231 @@
-use proc_macro2::TokenStream;
-use quote::quote;
+use proc_macro2::{Ident, TokenStream};
+use quote::{quote, quote_spanned};
+use syn::LitStr;
+use crate::types::{Machine, Message, Messages, State};
+use sfsm_base::TransitGuard;
+use sfsm_base::trace::{TraceHook, TraceKind, TraceMask};
+#[cfg(feature = "trace-binary")]
+use sfsm_base::trace::machine_id;
 
+#[cfg(any(
+    all(feature = "trace-defmt", feature = "trace-log"),
+    all(feature = "trace-defmt", feature = "trace-tracing"),
+    all(feature = "trace-defmt", feature = "trace-binary"),
+    all(feature = "trace-log", feature = "trace-tracing"),
+    all(feature = "trace-log", feature = "trace-binary"),
+    all(feature = "trace-tracing", feature = "trace-binary"),
+))]
+compile_error!("Only one of the trace-defmt, trace-log, trace-tracing and trace-binary features can be enabled at a time");
+
+/// The level a trace point is logged with if the selected backend knows about log levels.
+#[allow(dead_code)]
+enum Level {
+    Debug,
+    Trace,
+}
+
+/// Describes a single trace point. The log is the human readable representation used by the
+/// string based backends, while the fields are used by backends that support structured logging.
+/// The value is an expression implementing ``` Display ``` that is only known during runtime. It is
+/// appended to the log. The kind and the ids are used by the binary backend. The mask contains the
+/// categories the trace point is compiled in for, as set with the #[sfsm(...)] attributes. For
+/// messages, the mask of the state machine is only known as a constant of the state machine.
+#[allow(dead_code)]
+pub struct TracePoint {
+    machine: String,
+    action: &'static str,
+    log: String,
+    fields: Vec<(&'static str, String)>,
+    value: Option<(&'static str, TokenStream)>,
+    kind: TraceKind,
+    ids: Vec<TokenStream>,
+    mask: TraceMask,
+    machine_mask: Option<TokenStream>,
+}
+
+impl TracePoint {
+    /// A trace point that only concerns the state machine itself, like stopping it.
+    pub fn machine(machine: &Machine, kind: TraceKind) -> Self {
+        Self {
+            machine: machine.trace_name(),
+            action: kind.name(),
+            log: "".to_string(),
+            fields: vec![],
+            value: None,
+            kind,
+            ids: vec![],
+            mask: machine.options.trace,
+            machine_mask: None,
+        }
+    }
+
+    /// A trace point that concerns a single state, like entering or executing it.
+    pub fn state(machine: &Machine, kind: TraceKind, state: &State) -> Self {
+        let mask = machine.state_trace_mask(state);
+        let state_id = machine.state_id(state);
+        let state = state.get_name_type();
+        Self {
+            machine: machine.trace_name(),
+            action: kind.name(),
+            log: state.clone(),
+            fields: vec![("state", state)],
+            value: None,
+            kind,
+            ids: vec![quote! {#state_id}],
+            mask,
+            machine_mask: None,
+        }
+    }
+
+    /// A transition from one state into another.
+    pub fn transit(machine: &Machine, from: &State, to: &State) -> Self {
+        let mask = machine.transit_trace_mask(from, to);
+        let from_id = machine.state_id(from);
+        let to_id = machine.state_id(to);
+        let from = from.get_name_type();
+        let to = to.get_name_type();
+        Self {
+            machine: machine.trace_name(),
+            action: TraceKind::Transit.name(),
+            log: format!("From {} to {}", from, to),
+            fields: vec![("from", from), ("to", to)],
+            value: None,
+            kind: TraceKind::Transit,
+            ids: vec![quote! {#from_id}, quote! {#to_id}],
+            mask,
+            machine_mask: None,
+        }
+    }
+
+    /// The result of the guard of a transition from one state into another.
+    #[allow(dead_code)]
+    pub fn guard(machine: &Machine, from: &State, to: &State, guard: TransitGuard) -> Self {
+        let mask = machine.state_trace_mask(from) & machine.transit_trace_mask(from, to);
+        let from_id = machine.state_id(from);
+        let to_id = machine.state_id(to);
+        let guard_id = (guard == TransitGuard::Transit) as u8;
+        let from = from.get_name_type();
+        let to = to.get_name_type();
+        let guard = format!("{:?}", guard);
+        Self {
+            machine: machine.trace_name(),
+            action: TraceKind::Guard.name(),
+            log: format!("From {} to {}: {}", from, to, guard),
+            fields: vec![("from", from), ("to", to), ("guard", guard)],
+            value: None,
+            kind: TraceKind::Guard,
+            ids: vec![quote! {#from_id}, quote! {#to_id}, quote! {#guard_id}],
+            mask,
+            machine_mask: None,
+        }
+    }
+
+    /// A hook of a state that returned an error. The error is available as ``` err ``` and traced
+    /// as well if the trace-errors feature is enabled.
+    pub fn error(machine: &Machine, state: &State, hook: TraceHook) -> Self {
+        let state_id = machine.state_id(state);
+        let hook_id = hook as u8;
+        let state = state.get_name_type();
+        Self {
+            machine: machine.trace_name(),
+            action: TraceKind::Error.name(),
+            log: format!("From {}, {} failed", state, hook.name()),
+            fields: vec![("state", state), ("hook", hook.name().to_string())],
+            value: error_value(),
+            kind: TraceKind::Error,
+            ids: vec![quote! {#state_id}, quote! {#hook_id}],
+            mask: machine.options.trace,
+            machine_mask: None,
+        }
+    }
+
+    /// A message that is pushed to or polled from a state. As the messages are defined apart from
+    /// the state machine, the id of the state is determined during runtime. The payload is an
+    /// expression returning an ``` Option ``` of a reference to the message and traced as well if the
+    /// trace-payloads feature is enabled. Which trace points are compiled in for the state machine
+    /// is checked against its ``` TRACE_MASK ```.
+    pub fn message(messages: &Messages, kind: TraceKind, message: &Message, direction: &str, state: &State,
+                   payload: TokenStream, muted: bool) -> Self {
+        let name = &messages.name;
+        let message_id = messages.message_id(message);
+        let enum_name = &messages.enum_name;
+        let enum_entry = &state.enum_name;
+        let message = message.get_name_type();
+        let state = state.get_name_type();
+        Self {
+            machine: messages.trace_name(),
+            action: kind.name(),
+            log: format!("{} {} {}", message, direction, state),
+            fields: vec![("message", message), ("state", state)],
+            value: payload_value(payload),
+            kind,
+            ids: vec![quote! {#message_id}, quote! {#enum_name::#enum_entry(None).state_id()}],
+            mask: if muted { TraceMask::NONE } else { TraceMask::ALL },
+            machine_mask: Some(quote! {#name::TRACE_MASK}),
+        }
+    }
+}
+
+/// Formats the error with its ``` TraceFormat ``` or ``` Debug ``` implementation.
+#[cfg(not(feature = "trace-errors"))]
+fn error_value() -> Option<(&'static str, TokenStream)> {
+    None
+}
+
+#[cfg(feature = "trace-errors")]
+fn error_value() -> Option<(&'static str, TokenStream)> {
+    Some(("error", quote! {
+        TraceDisplay(|f: &mut ::core::fmt::Formatter<'_>| (&&&TraceValue(&err)).format_trace(f))
+    }))
+}
+
+/// Formats the message with its ``` TraceFormat ``` or ``` Debug ``` implementation.
+#[cfg(not(feature = "trace-payloads"))]
+fn payload_value(_payload: TokenStream) -> Option<(&'static str, TokenStream)> {
+    None
+}
+
+#[cfg(feature = "trace-payloads")]
+fn payload_value(payload: TokenStream) -> Option<(&'static str, TokenStream)> {
+    Some(("payload", quote! {
+        TraceDisplay(|f: &mut ::core::fmt::Formatter<'_>| match #payload {
+            Some(message) => (&&&TraceValue(message)).format_trace(f),
+            None => Ok(()),
+        })
+    }))
+}
+
+/// Reads the clock registered with ``` #[sfsm_clock] ``` if the trace-time feature is enabled.
+#[cfg(not(feature = "trace-time"))]
+fn timestamp() -> Option<TokenStream> {
+    None
+}
+
+#[cfg(feature = "trace-time")]
+fn timestamp() -> Option<TokenStream> {
+    Some(quote! { __sfsm_clock() })
+}
+
+/// Returns the format string and the arguments of the log for the backends that format the logs
+/// during runtime. The timestamp and the step precede the log and the value is appended to it.
+#[allow(dead_code)]
+fn format_args(point: TracePoint) -> (String, Vec<TokenStream>) {
+    let log = format_log(&point.machine, point.action, &point.log);
+    let mut format = "{}".to_string();
+    let mut args = vec![quote! { #log }];
+    if let Some((_, value)) = point.value {
+        format.push_str(": {}");
+        args.push(value);
+    }
+    if let Some(timestamp) = timestamp() {
+        format = format!("[{{}} #{{}}] {}", format);
+        args.insert(0, quote! { trace_context.step });
+        args.insert(0, timestamp);
+    }
+    (format, args)
+}
+
+#[allow(dead_code)]
 pub fn format_log(sfsm_name: &str, action: &str, log: &str) -> String {
     if log.len() > 0 {
         return format!("{}: {} - {}", sfsm_name, action, log);
@@ -10,15 +235,15 @@ pub fn format_log(sfsm_name: &str, action: &str, log: &str) -> String {
 }
 
 #[cfg(not(feature = "trace"))]
-pub fn trace(_str: String) -> TokenStream {
+pub fn trace(_point: TracePoint) -> TokenStream {
     proc_macro2::TokenStream::from(
         quote! {
         }
     )
 }
-    
+
 #[cfg(not(feature = "trace-steps"))]
-pub fn step(_str: String) -> TokenStream {
+pub fn step(_point: TracePoint) -> TokenStream {
     proc_macro2::TokenStream::from(
         quote! {
         }
@@ -26,7 +251,7 @@ pub fn step(_str: String) -> TokenStream {
 }
 
 #[cfg(not(feature = "trace-messages"))]
-pub fn message(_str: String) -> TokenStream {
+pub fn message(_point: TracePoint) -> TokenStream {
     proc_macro2::TokenStream::from(
         quote! {
         }
@@ -34,28 +259,510 @@ pub fn message(_str: String) -> TokenStream {
 }
 
 #[cfg(feature = "trace")]
-pub fn trace(str: String) -> TokenStream {
-    proc_macro2::TokenStream::from(
+pub fn trace(point: TracePoint) -> TokenStream {
+    filter(TraceMask::TRANSITIONS, quote! {TraceMask::TRANSITIONS}, Level::Debug, point)
+}
+
+#[cfg(feature = "trace-steps")]
+pub fn step(point: TracePoint) -> TokenStream {
+    filter(TraceMask::STEPS, quote! {TraceMask::STEPS}, Level::Trace, point)
+}
+
+#[cfg(feature = "trace-messages")]
+pub fn message(point: TracePoint) -> TokenStream {
+    filter(TraceMask::MESSAGES, quote! {TraceMask::MESSAGES}, Level::Debug, point)
+}
+
+/// Traces the result of the guard of a transition. The guard is evaluated only once and its result
+/// is returned, so the trace point can be placed in the condition of the transition. If records are
+/// enabled, the result is recorded as well.
+#[cfg(not(feature = "trace-guards"))]
+pub fn guard(_machine: &Machine, _from: &State, _to: &State, guard: TokenStream) -> TokenStream {
+    guard
+}
+
+#[cfg(feature = "trace-guards")]
+pub fn guard(machine: &Machine, from: &State, to: &State, guard: TokenStream) -> TokenStream {
+    let name = &machine.name;
+    let trace_transit = filter(TraceMask::GUARDS, quote! {TraceMask::GUARDS}, Level::Trace,
+                               TracePoint::guard(machine, from, to, TransitGuard::Transit));
+    let trace_remain = filter(TraceMask::GUARDS, quote! {TraceMask::GUARDS}, Level::Trace,
+                              TracePoint::guard(machine, from, to, TransitGuard::Remain));
+    let trace_guard = quote! {
+        match guard {
+            TransitGuard::Transit => { #trace_transit }
+            TransitGuard::Remain => { #trace_remain }
+        }
+    };
+    let from_id = machine.state_id(from);
+    let to_id = machine.state_id(to);
+    let record_guard = record(name, quote! {
+        TraceEvent::Guard { from: #from_id, to: #to_id, guard }
+    });
+    quote! {
+        {
+            let guard = #guard;
+            #trace_guard
+            #record_guard
+            guard
+        }
+    }
+}
+
+/// Only compiles the trace point in if its category is enabled with the #[sfsm(...)] attributes
+/// and only traces it if the category is enabled in the trace mask of the state machine.
+#[allow(dead_code)]
+fn filter(category: TraceMask, mask: TokenStream, level: Level, point: TracePoint) -> TokenStream {
+    if !point.mask.contains(category) {
+        return quote! {};
+    }
+    let machine_mask = point.machine_mask.clone();
+    let tokens = emit(level, point);
+    match machine_mask {
+        Some(machine_mask) => quote! {
+            if #machine_mask.contains(#mask) && trace_context.mask.contains(#mask) {
+                #tokens
+            }
+        },
+        None => quote! {
+            if trace_context.mask.contains(#mask) {
+                #tokens
+            }
+        },
+    }
+}
+
+/// True if any trace point is compiled in. Only then the state machines store a trace context.
+const ENABLED: bool = cfg!(any(feature = "trace", feature = "trace-steps", feature = "trace-messages",
+                               feature = "trace-guards", feature = "trace-record"));
+
+/// The field the trace context is stored in.
+pub fn context_field() -> TokenStream {
+    if ENABLED {
         quote! {
-            __sfsm_trace(#str);
+            trace_context: TraceContext,
         }
-    )
+    } else {
+        quote! {}
+    }
 }
 
-#[cfg(feature = "trace-steps")]
-pub fn step(str: String) -> TokenStream {
-    proc_macro2::TokenStream::from(
+/// Initializes the trace context with all trace points enabled.
+pub fn context_init() -> TokenStream {
+    if ENABLED {
         quote! {
-            __sfsm_trace(#str);
+            trace_context: TraceContext::new(),
         }
-    )
+    } else {
+        quote! {}
+    }
 }
 
-#[cfg(feature = "trace-messages")]
-pub fn message(str: String) -> TokenStream {
-    proc_macro2::TokenStream::from(
+/// The parameter to pass the trace context into the inner functions that contain trace points.
+pub fn context_param() -> TokenStream {
+    if ENABLED {
         quote! {
-            __sfsm_trace(#str);
+            , trace_context: TraceContext
         }
-    )
+    } else {
+        quote! {}
+    }
+}
+
+/// The argument to pass the trace context into the inner functions that contain trace points.
+pub fn context_arg() -> TokenStream {
+    if ENABLED {
+        quote! {
+            , trace_context
+        }
+    } else {
+        quote! {}
+    }
+}
+
+/// Counts the steps of the state machine.
+pub fn count_step() -> TokenStream {
+    if ENABLED {
+        quote! {
+            self.trace_context.step = self.trace_context.step.wrapping_add(1);
+        }
+    } else {
+        quote! {}
+    }
+}
+
+/// Generates the code that has to run before the state machine is accessed. It makes the trace
+/// context available to the trace points and enters the span of the state machine if the tracing
+/// backend is used.
+pub fn enter(name: &str) -> TokenStream {
+    let context = if ENABLED {
+        quote! {
+            #[allow(unused_variables)]
+            let trace_context = self.trace_context;
+        }
+    } else {
+        quote! {}
+    };
+    let span = span(name);
+    quote! {
+        #context
+        #span
+    }
+}
+
+/// Makes the name the state machine is traced with and the categories of trace points compiled in
+/// for it available to the messages, which are defined apart from the state machine.
+pub fn constants(machine: &Machine) -> TokenStream {
+    if ENABLED {
+        let name = &machine.name;
+        let trace_name = machine.trace_name();
+        let trace_mask = machine.options.trace.bits();
+        quote! {
+            impl #name {
+                #[doc(hidden)]
+                pub const TRACE_NAME: &'static str = #trace_name;
+                #[doc(hidden)]
+                pub const TRACE_MASK: TraceMask = TraceMask::from_bits(#trace_mask);
+            }
+        }
+    } else {
+        quote! {}
+    }
+}
+
+/// Checks that the parent of a nested state machine is a state machine and that the path of the
+/// parent is the path the parent is traced with. Otherwise the path does not match the nesting.
+pub fn check_parent(machine: &Machine) -> TokenStream {
+    let (parent, path) = match (machine.options.parent_machine(), &machine.options.parent) {
+        (Some(parent), Some(path)) => (parent, path.value()),
+        _ => return quote! {},
+    };
+    let name_check = if ENABLED {
+        let message = format!("The parent \"{}\" of {} must be the path {} is traced with, including the parent of {}",
+                              path, machine.name, parent, parent);
+        quote_spanned! { parent.span() =>
+            const _: () = assert!(trace_names_match(#parent::TRACE_NAME, #path), #message);
+        }
+    } else {
+        quote! {}
+    };
+    quote_spanned! { parent.span() =>
+        const _: fn() = || {
+            fn parent_must_be_a_state_machine<T: StateMachine>() {}
+            parent_must_be_a_state_machine::<#parent>();
+        };
+        #name_check
+    }
+}
+
+/// Checks that the messages are traced with the path of their state machine. The parent of the
+/// messages is only known from the state machine if the states were requested from it for a
+/// broadcast to all states. Otherwise it has to be repeated with #[sfsm(parent = "...")].
+pub fn check_messages_parent(messages: &Messages) -> TokenStream {
+    if !ENABLED {
+        return quote! {};
+    }
+    let name = &messages.name;
+    let trace_name = messages.trace_name();
+    let message = format!("The messages of {} must be traced with the parent of {}. Set it with #[sfsm(parent = \"...\")]",
+                          name, name);
+    let span = messages.options.parent.as_ref().map_or(name.span(), LitStr::span);
+    quote_spanned! { span =>
+        const _: () = assert!(trace_names_match(#name::TRACE_NAME, #trace_name), #message);
+    }
+}
+
+/// Implements the TraceControl trait. Without any trace points, there is nothing to control.
+pub fn control(machine: &Ident) -> TokenStream {
+    if ENABLED {
+        quote! {
+            impl TraceControl for #machine {
+                fn set_trace_mask(&mut self, mask: TraceMask) {
+                    self.trace_context.mask = mask;
+                }
+
+                fn trace_mask(&self) -> TraceMask {
+                    self.trace_context.mask
+                }
+
+                fn set_trace_instance(&mut self, instance: u16) {
+                    self.trace_context.instance = instance;
+                }
+
+                fn trace_instance(&self) -> u16 {
+                    self.trace_context.instance
+                }
+            }
+        }
+    } else {
+        quote! {
+            impl TraceControl for #machine {
+                fn set_trace_mask(&mut self, _mask: TraceMask) {}
+
+                fn trace_mask(&self) -> TraceMask {
+                    TraceMask::NONE
+                }
+
+                fn set_trace_instance(&mut self, _instance: u16) {}
+
+                fn trace_instance(&self) -> u16 {
+                    0
+                }
+            }
+        }
+    }
+}
+
+/// Generates the description of the state machine that is referred to by the records.
+#[cfg(not(feature = "trace-record"))]
+pub fn info(_machine: &Machine) -> TokenStream {
+    quote! {}
+}
+
+#[cfg(feature = "trace-record")]
+pub fn info(machine: &Machine) -> TokenStream {
+    let name = machine.trace_name();
+    let state_names: Vec<String> = machine.states.iter().map(|state| state.get_name_type()).collect();
+    let error_state = match &machine.error_state {
+        Some(error_state) => {
+            let error_state = machine.state_id(error_state);
+            quote! { Some(#error_state) }
+        },
+        None => quote! { None },
+    };
+    let machine = &machine.name;
+    quote! {
+        impl #machine {
+            /// Describes the state machine in the records
+            pub const TRACE_INFO: &'static MachineInfo = &MachineInfo {
+                name: #name,
+                states: &[#( #state_names ),*],
+                error_state: #error_state,
+            };
+        }
+    }
+}
+
+/// Passes a record of the event to the function registered with ``` #[sfsm_record] ```.
+/// The records are not filtered by the trace mask.
+#[cfg(not(feature = "trace-record"))]
+pub fn record(_machine: &Ident, _event: TokenStream) -> TokenStream {
+    quote! {}
+}
+
+#[cfg(feature = "trace-record")]
+pub fn record(machine: &Ident, event: TokenStream) -> TokenStream {
+    let timestamp = match timestamp() {
+        Some(timestamp) => quote! { Some(#timestamp) },
+        None => quote! { None },
+    };
+    quote! {
+        __sfsm_record(&TraceRecord {
+            machine: #machine::TRACE_INFO,
+            instance: trace_context.instance,
+            step: trace_context.step,
+            timestamp: #timestamp,
+            event: #event,
+        });
+    }
+}
+
+/// Enters a span named after the state machine if the tracing backend is used.
+#[cfg(not(feature = "trace-tracing"))]
+fn span(_name: &str) -> TokenStream {
+    quote! {
+    }
+}
+
+#[cfg(feature = "trace-tracing")]
+fn span(machine: &str) -> TokenStream {
+    quote! {
+        let _sfsm_span = ::tracing::debug_span!(#machine, machine = #machine).entered();
+    }
+}
+
+/// Forwards the log to the logger function registered with ``` #[sfsm_trace] ```.
+#[cfg(not(any(feature = "trace-defmt", feature = "trace-log", feature = "trace-tracing", feature = "trace-binary")))]
+#[allow(dead_code)]
+fn emit(_level: Level, point: TracePoint) -> TokenStream {
+    let log = format_log(&point.machine, point.action, &point.log);
+    let (format, args) = format_args(point);
+    if args.len() > 1 {
+        quote! {
+            let mut buffer = TraceBuffer::new();
+            let _ = ::core::fmt::Write::write_fmt(&mut buffer, format_args!(#format, #( #args ),*));
+            __sfsm_trace(buffer.as_str());
+        }
+    } else {
+        quote! {
+            __sfsm_trace(#log);
+        }
+    }
+}
+
+/// Emits the log as a defmt frame. The whole log is used as format string, so it gets interned
+/// and only its index has to be transmitted.
+#[cfg(feature = "trace-defmt")]
+fn emit(level: Level, point: TracePoint) -> TokenStream {
+    let log = format_log(&point.machine, point.action, &point.log);
+    let mut format = log.replace('{', "{{").replace('}', "}}");
+    let mut args = match point.value {
+        Some((_, value)) => {
+            format.push_str(": {}");
+            quote! { , ::defmt::Display2Format(&#value) }
+        },
+        None => quote! {},
+    };
+    if let Some(timestamp) = timestamp() {
+        format = format!("[{{=u64}} #{{=u32}}] {}", format);
+        args = quote! { , #timestamp, trace_context.step #args };
+    }
+    match level {
+        Level::Debug => quote! {
+            ::defmt::debug!(#format #args);
+        },
+        Level::Trace => quote! {
+            ::defmt::trace!(#format #args);
+        },
+    }
+}
+
+/// Forwards the log to the log facade.
+#[cfg(feature = "trace-log")]
+fn emit(level: Level, point: TracePoint) -> TokenStream {
+    let (format, args) = format_args(point);
+    match level {
+        Level::Debug => quote! {
+            ::log::debug!(#format, #( #args ),*);
+        },
+        Level::Trace => quote! {
+            ::log::trace!(#format, #( #args ),*);
+        },
+    }
+}
+
+/// Emits a tracing event with the action as message and the machine, states and messages
+/// involved as fields.
+#[cfg(feature = "trace-tracing")]
+fn emit(level: Level, point: TracePoint) -> TokenStream {
+    let machine = &point.machine;
+    let action = point.action;
+    let names: Vec<Ident> = point.fields.iter().map(|(name, _)| {
+        Ident::new(name, proc_macro2::Span::call_site())
+    }).collect();
+    let values: Vec<&String> = point.fields.iter().map(|(_, value)| value).collect();
+    let value = match point.value {
+        Some((name, value)) => {
+            let name = Ident::new(name, proc_macro2::Span::call_site());
+            quote! { #name = %#value, }
+        },
+        None => quote! {},
+    };
+    let time = match timestamp() {
+        Some(timestamp) => quote! { timestamp = #timestamp, step = trace_context.step, },
+        None => quote! {},
+    };
+    match level {
+        Level::Debug => quote! {
+            ::tracing::debug!(machine = #machine, #( #names = #values, )* #value #time #action);
+        },
+        Level::Trace => quote! {
+            ::tracing::trace!(machine = #machine, #( #names = #values, )* #value #time #action);
+        },
+    }
+}
+
+/// Emits the trace point as a frame of a few bytes. It contains the id of the state machine, the
+/// kind of the trace point and the ids of the states and messages. Values like errors are not
+/// part of the frames.
+#[cfg(feature = "trace-binary")]
+fn emit(_level: Level, point: TracePoint) -> TokenStream {
+    let [id_low, id_high] = machine_id(&point.machine).to_le_bytes();
+    let kind = point.kind as u8;
+    let ids = point.ids;
+    match timestamp() {
+        Some(timestamp) => {
+            let kind = kind | TraceKind::TIME_FLAG;
+            quote! {
+                let mut frame = TraceFrame::new(&[#id_low, #id_high, #kind #(, #ids)*]);
+                frame.push_varint(#timestamp);
+                frame.push_varint(trace_context.step as u64);
+                __sfsm_trace_binary(frame.as_bytes());
+            }
+        },
+        None => quote! {
+            __sfsm_trace_binary(&[#id_low, #id_high, #kind #(, #ids)*]);
+        },
+    }
+}
+
+/// Generates the dictionary the host side decoder needs to turn the binary traces of the state
+/// machine back into logs. It is a JSON object with the id of the state machine, its name and the
+/// names of its states.
+#[cfg(not(feature = "trace-binary"))]
+pub fn dictionary(_machine: &Machine) -> TokenStream {
+    quote! {}
+}
+
+#[cfg(feature = "trace-binary")]
+pub fn dictionary(machine: &Machine) -> TokenStream {
+    let name = &machine.name;
+    let trace_name = machine.trace_name();
+    let states: Vec<String> = machine.states.iter().map(|state| json_string(&state.get_name_type())).collect();
+    let dictionary = format!("{{\"id\":{},\"name\":{},\"states\":[{}]}}",
+                             machine_id(&trace_name), json_string(&trace_name), states.join(","));
+    quote! {
+        impl #name {
+            /// The dictionary to decode the binary traces of the state machine
+            pub const TRACE_DICTIONARY: &'static str = #dictionary;
+        }
+    }
+}
+
+/// Reserves the id of the state machine in the crate. The ids are folded from the names, so two
+/// state machines can end up with the same id and could not be told apart in the binary traces.
+/// The exported macros share the namespace of the crate root, so a second state machine with the
+/// same id fails to compile with an error at both of them.
+#[cfg(not(feature = "trace-binary"))]
+pub fn check_id(_machine: &Machine) -> TokenStream {
+    quote! {}
+}
+
+#[cfg(feature = "trace-binary")]
+pub fn check_id(machine: &Machine) -> TokenStream {
+    let name = &machine.name;
+    let id_macro = Ident::new(&format!("__sfsm_machines_share_trace_id_{}", machine_id(&machine.trace_name())), name.span());
+    quote_spanned! { name.span() =>
+        #[doc(hidden)]
+        #[macro_export]
+        macro_rules! #id_macro {
+            () => {}
+        }
+    }
+}
+
+/// Generates the dictionary of the messages of a state machine. It is a JSON object with the id of
+/// the state machine and the names of the messages.
+#[cfg(not(feature = "trace-binary"))]
+pub fn messages_dictionary(_messages: &Messages) -> TokenStream {
+    quote! {}
+}
+
+#[cfg(feature = "trace-binary")]
+pub fn messages_dictionary(messages: &Messages) -> TokenStream {
+    let name = &messages.name;
+    let message_names: Vec<String> = messages.message_names().iter().map(|message| json_string(message)).collect();
+    let dictionary = format!("{{\"id\":{},\"messages\":[{}]}}",
+                             machine_id(&messages.trace_name()), message_names.join(","));
+    quote! {
+        impl #name {
+            /// The dictionary to decode the messages in the binary traces of the state machine
+            pub const TRACE_MESSAGES_DICTIONARY: &'static str = #dictionary;
+        }
+    }
+}
+
+#[cfg(feature = "trace-binary")]
+fn json_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
 }
@@ -1,7 +1,8 @@
 use convert_case::{Case, Casing};
-use proc_macro2::{Ident, Span, TokenStream};
+use proc_macro2::{Ident, Span, TokenStream, TokenTree};
 use quote::{ToTokens, quote};
-use syn::{AngleBracketedGenericArguments, Visibility, Attribute, TypePath};
+use syn::{AngleBracketedGenericArguments, Visibility, Attribute, TypePath, Lifetime, LitStr};
+use sfsm_base::trace::TraceMask;
 
 pub enum Mode {
     NonFallible,
@@ -37,6 +38,12 @@ pub struct State {
 
 impl State {
     pub fn state_to_enum(name: &Ident, types: &Option<AngleBracketedGenericArguments>) -> Ident {
+        State::type_to_ident(name, types, "State")
+    }
+
+    /// Turns a type with optional generic arguments into an identifier like FooBarState for
+    /// Foo<Bar> with the suffix State.
+    pub fn type_to_ident(name: &Ident, types: &Option<AngleBracketedGenericArguments>, suffix: &str) -> Ident {
         let args_string = if let Some(args) = types {
             let mut args_string = args.into_token_stream().to_string();
             args_string = str::replace(args_string.as_str(), "'", "");
@@ -51,8 +58,8 @@ impl State {
         } else {
             "".to_string()
         };
-        Ident::new(format!("{}{}State", name.to_string(), args_string).as_str(),
-                   Span::call_site())
+        Ident::new(format!("{}{}{}", name.to_string(), args_string, suffix).as_str(),
+                   name.span())
     }
 
     pub fn get_name_type(&self) -> String {
@@ -68,10 +75,60 @@ impl State {
 pub struct Transition {
     pub src: State,
     pub dst: State,
+    pub muted: bool,
+}
+
+/// Contains the options that can be set with the #[sfsm(...)] attribute
+#[derive(Default)]
+pub struct Options {
+    /// The path of the state machine this state machine is nested in, like "ForwardObserver" or
+    /// "ForwardObserver/Online". It must be the path the parent state machine is traced with.
+    pub parent: Option<LitStr>,
+    /// The trace points that are compiled in for this state machine, like "transitions, messages"
+    pub trace: TraceMask,
+    /// Set on states, transitions and messages that are never traced
+    pub mute: bool,
+    /// The capacity of the message queue of the state machine
+    pub queue: Option<usize>,
+    /// The number of deferred messages the state machine can hold
+    pub defer: Option<usize>,
+    /// Set on messages that are held until their target state is active
+    pub deferred: bool,
+    /// Set on messages that the state can fail to receive
+    pub fallible: bool,
+    /// Set if the message enum of the state machine is generated
+    pub dispatch: bool,
+    /// Set if the message enum is decoded from and encoded into frames. Implies dispatch.
+    pub codec: bool,
+    /// The tag a message is pinned to in the frames of the codec
+    pub tag: Option<u8>,
+}
+
+impl Options {
+    /// Returns the name the state machine is traced with. Nested state machines are prefixed
+    /// with the path of their parent.
+    pub fn trace_name(&self, name: &Ident) -> String {
+        match &self.parent {
+            Some(parent) => format!("{}/{}", parent.value(), name),
+            None => name.to_string(),
+        }
+    }
+
+    /// Returns the parent state machine, which is the last part of the path of the parent
+    pub fn parent_machine(&self) -> Option<Ident> {
+        self.parent.as_ref().map(|parent| {
+            let value = parent.value();
+            let name = value.rsplit('/').next().expect("Internal error. The path of the parent can not be empty");
+            Ident::new(name, parent.span())
+        })
+    }
 }
 
 // Contains all data required to generate the state machine
 pub struct Machine {
+    pub options: Options,
+    pub muted_states: Vec<Ident>,
+    pub muted_transitions: Vec<(Ident, Ident)>,
     pub attributes: Vec<Attribute>,
     pub visibility: Option<Visibility>,
     pub name: Ident,
@@ -83,6 +140,51 @@ pub struct Machine {
     pub trait_definitions: TraitDefinitions,
     pub mode: Mode,
     pub error_state: Option<State>,
+    /// The messages declared together with the state machine
+    pub messages: Option<Messages>,
+}
+
+impl Machine {
+    /// Returns the id of a state, which is its index in the list of states.
+    pub fn state_id(&self, state: &State) -> u8 {
+        self.states.iter().position(|candidate| {
+            candidate.enum_name == state.enum_name
+        }).expect("Internal error. Expected to find the state in the list of states") as u8
+    }
+
+    /// Returns the name the state machine is traced with
+    pub fn trace_name(&self) -> String {
+        self.options.trace_name(&self.name)
+    }
+
+    /// Returns the name of the enum of the messages that is generated by add_messages!
+    pub fn messages_enum_name(&self) -> Ident {
+        Ident::new(&format!("{}Messages", self.name), Span::call_site())
+    }
+
+    /// Returns the name of the macro that passes the states of the state machine to add_messages!.
+    /// It is exported with the name of the state machine.
+    pub fn states_macro_name(name: &Ident) -> Ident {
+        Ident::new(&format!("__sfsm_states_{}", name), Span::call_site())
+    }
+
+    /// Returns the trace points that are compiled in for a state. Muted states are not traced.
+    pub fn state_trace_mask(&self, state: &State) -> TraceMask {
+        if self.muted_states.contains(&state.enum_name) {
+            return TraceMask::NONE;
+        }
+        self.options.trace
+    }
+
+    /// Returns the trace points that are compiled in for a transition. Muted transitions are not
+    /// traced.
+    pub fn transit_trace_mask(&self, from: &State, to: &State) -> TraceMask {
+        let transition = (from.enum_name.clone(), to.enum_name.clone());
+        if self.muted_transitions.contains(&transition) {
+            return TraceMask::NONE;
+        }
+        self.options.trace
+    }
 }
 
 // Contains data needed to generate generate a enum entry for a state
@@ -98,6 +200,7 @@ pub struct MatchStateEntry {
 }
 
 // The actual message containing the struct name and optional generics arguments
+#[derive(Clone)]
 pub struct Message {
     pub generics: Option<AngleBracketedGenericArguments>,
     pub name: Ident,
@@ -112,12 +215,98 @@ impl Message {
             #name#generics
         }).to_string()
     }
+
+    /// Returns the name of the variant of the message in the generated message enum
+    pub fn variant_name(&self) -> Ident {
+        State::type_to_ident(&self.name, &self.generics, "")
+    }
+
+    /// Returns all lifetimes in the generic arguments of the message, like 'a in Frame<'a> or
+    /// in Command<&'a str>.
+    pub fn all_lifetimes(&self) -> Vec<Lifetime> {
+        fn collect(tokens: TokenStream, lifetimes: &mut Vec<Lifetime>) {
+            let mut tokens = tokens.into_iter().peekable();
+            while let Some(token) = tokens.next() {
+                match token {
+                    TokenTree::Punct(punct) if punct.as_char() == '\'' => {
+                        if let Some(TokenTree::Ident(ident)) = tokens.peek() {
+                            let lifetime = Lifetime::new(&format!("'{}", ident), punct.span());
+                            if !lifetimes.contains(&lifetime) {
+                                lifetimes.push(lifetime);
+                            }
+                            tokens.next();
+                        }
+                    }
+                    TokenTree::Group(group) => collect(group.stream(), lifetimes),
+                    _ => {}
+                }
+            }
+        }
+        let mut lifetimes = vec![];
+        collect(self.generics.to_token_stream(), &mut lifetimes);
+        lifetimes
+    }
+
+    /// Returns the lifetimes the message borrows its data for. The generated implementations
+    /// are generic over them.
+    pub fn lifetimes(&self) -> Vec<Lifetime> {
+        self.all_lifetimes().into_iter().filter(|lifetime| lifetime.ident != "static").collect()
+    }
+
+    /// Returns true if the message borrows data and can therefore not be held by the state
+    /// machine.
+    pub fn is_borrowed(&self) -> bool {
+        !self.lifetimes().is_empty()
+    }
 }
 
 // Enum containing the direction of the message. Can be either a push or poll message
+#[derive(Clone)]
 pub enum MessageDir {
     Push(Message),
     Poll(Message),
+    Request(Message, Message),
+}
+
+impl MessageDir {
+    /// Returns the pushed or polled message or the request
+    pub fn message(&self) -> &Message {
+        match self {
+            MessageDir::Push(message) | MessageDir::Poll(message) | MessageDir::Request(message, _) => message,
+        }
+    }
+
+    /// Returns all message types, which includes the reply of a request
+    pub fn messages(&self) -> Vec<&Message> {
+        match self {
+            MessageDir::Push(message) | MessageDir::Poll(message) => vec![message],
+            MessageDir::Request(request, reply) => vec![request, reply],
+        }
+    }
+
+    /// Returns the lifetimes of the message types as the generic parameters of an impl block,
+    /// like <'a>, or nothing if the messages do not borrow any data.
+    pub fn impl_generics(&self) -> TokenStream {
+        let mut lifetimes: Vec<Lifetime> = vec![];
+        for lifetime in self.messages().into_iter().flat_map(|message| message.lifetimes()) {
+            if !lifetimes.contains(&lifetime) {
+                lifetimes.push(lifetime);
+            }
+        }
+        if lifetimes.is_empty() {
+            return quote! {};
+        }
+        quote! { <#(#lifetimes),*> }
+    }
+
+    /// Returns the message if it is pushed or polled as requested
+    pub fn directed(&self, push: bool) -> Option<&Message> {
+        match self {
+            MessageDir::Push(message) if push => Some(message),
+            MessageDir::Poll(message) if !push => Some(message),
+            _ => None,
+        }
+    }
 }
 
 // Contains the target state plus all message information used to generate the necessary trait
@@ -125,13 +314,198 @@ pub enum MessageDir {
 pub struct StateMessage {
     pub state: State,
     pub message: MessageDir,
+    pub muted: bool,
+    pub deferred: bool,
+    pub fallible: bool,
+}
+
+// The states a message is pushed into or polled from. A list of states or all states with *
+// broadcast the message to whichever of the states is active.
+pub enum MessageTargets {
+    State(State),
+    States(Vec<State>),
+    All,
+}
+
+// A message definition as it was written in add_messages!, before the broadcasts are expanded
+pub struct MessageDefinition {
+    pub message: MessageDir,
+    pub targets: MessageTargets,
+    pub muted: bool,
+    pub deferred: bool,
+    pub fallible: bool,
+    pub tag: Option<u8>,
+}
+
+// A message broadcast to a list of states or to all states
+pub struct Broadcast {
+    pub message: MessageDir,
+    pub states: Vec<State>,
+    pub all: bool,
+    pub muted: bool,
 }
 
 // The whole message that will be used to generate the macro outputs
 pub struct Messages {
+    pub options: Options,
+    /// The attributes that are added to the message enum
+    pub attributes: Vec<Attribute>,
+    pub visibility: Option<Visibility>,
     pub name: Ident,
     pub enum_name: Ident,
+    pub messages_enum_name: Ident,
     pub messages: Vec<StateMessage>,
+    pub broadcasts: Vec<Broadcast>,
+    /// The messages whose tags are pinned with #[sfsm(tag = ...)]
+    pub tags: Vec<(Message, u8)>,
+    /// Set if messages are broadcast to all states, but the states of the state machine are not
+    /// known yet. They are then requested from the macro generated by add_state_machine!.
+    pub requires_states: bool,
+    /// The path add_messages! is called back with once the states are known. The sfsm crate
+    /// passes its own add_messages! with $crate, so it resolves even if sfsm is renamed.
+    pub callback: Option<TokenStream>,
+}
+
+impl Messages {
+    /// Returns the name the state machine is traced with
+    pub fn trace_name(&self) -> String {
+        self.options.trace_name(&self.name)
+    }
+
+    /// Returns the id of a message type, which is its index in the list of distinct message types
+    /// in the order they were defined.
+    pub fn message_id(&self, message: &Message) -> u8 {
+        self.message_names().iter().position(|name| *name == message.get_name_type())
+            .expect("Internal error. Expected to find the message in the list of messages") as u8
+    }
+
+    /// Returns the distinct message types that are pushed into the states together with the states
+    /// that receive them. Messages broadcast to all states have no receiving states of their own.
+    /// Fallible messages are left out, as their errors are handled by the state machine, and so are
+    /// borrowed messages, as the state machine can not hold them.
+    pub fn push_messages(&self) -> Vec<(&Message, Vec<&State>)> {
+        self.directed_messages(true)
+    }
+
+    /// Returns the distinct message types that are polled from the states together with the states
+    /// that return them. Messages broadcast to all states have no returning states of their own.
+    /// Borrowed messages are left out.
+    pub fn poll_messages(&self) -> Vec<(&Message, Vec<&State>)> {
+        self.directed_messages(false)
+    }
+
+    fn directed_messages(&self, push: bool) -> Vec<(&Message, Vec<&State>)> {
+        let mut messages: Vec<(&Message, Vec<&State>)> = vec![];
+        for state_message in self.messages.iter().filter(|state_message| !state_message.fallible) {
+            if let Some(message) = state_message.message.directed(push).filter(|message| !message.is_borrowed()) {
+                match messages.iter_mut().find(|(known, _)| known.get_name_type() == message.get_name_type()) {
+                    Some((_, states)) => states.push(&state_message.state),
+                    None => messages.push((message, vec![&state_message.state])),
+                }
+            }
+        }
+        for broadcast in &self.broadcasts {
+            if let Some(message) = broadcast.message.directed(push).filter(|message| !message.is_borrowed()) {
+                if !messages.iter().any(|(known, _)| known.get_name_type() == message.get_name_type()) {
+                    messages.push((message, vec![]));
+                }
+            }
+        }
+        messages
+    }
+
+    /// Returns the distinct message types that are encoded by the codec together with their tags.
+    /// The pinned messages keep their tags, the others get the lowest free tags in the order of
+    /// their ids. A tag is a single byte, so the tags run out after 256 messages.
+    pub fn codec_tags(&self) -> syn::Result<Vec<(&Message, u8)>> {
+        let mut free_tags = (0..=u8::MAX).filter(|tag| !self.tags.iter().any(|(_, pinned)| pinned == tag));
+        let mut tags: Vec<(&Message, u8)> = vec![];
+        for message in self.enum_messages() {
+            let pinned = self.tags.iter().find(|(known, _)| known.get_name_type() == message.get_name_type());
+            let tag = match pinned {
+                Some((_, tag)) => *tag,
+                None => free_tags.next().ok_or_else(|| syn::Error::new_spanned(&message.name,
+                    format!("There is no tag left for {}. The codec can encode at most 256 messages", message.get_name_type())))?,
+            };
+            tags.push((message, tag));
+        }
+        Ok(tags)
+    }
+
+    /// Returns the distinct message types that are held in the message enum. Requests are answered
+    /// right away, so they are not part of it.
+    pub fn enum_messages(&self) -> Vec<&Message> {
+        let push_messages = self.push_messages();
+        let poll_messages = self.poll_messages();
+        self.message_types().into_iter().filter(|message| {
+            push_messages.iter().chain(poll_messages.iter()).any(|(known, _)| known.get_name_type() == message.get_name_type())
+        }).collect()
+    }
+
+    /// Returns the distinct message types, in the order of their ids.
+    pub fn message_types(&self) -> Vec<&Message> {
+        let mut types: Vec<&Message> = vec![];
+        let messages = self.messages.iter().map(|state_message| &state_message.message)
+            .chain(self.broadcasts.iter().map(|broadcast| &broadcast.message));
+        for message in messages.flat_map(|message| message.messages()) {
+            if !types.iter().any(|known| known.get_name_type() == message.get_name_type()) {
+                types.push(message);
+            }
+        }
+        types
+    }
+
+    /// Returns the broadcast of the message type in the given direction, if it is broadcast.
+    pub fn broadcast(&self, message: &Message, push: bool) -> Option<&Broadcast> {
+        self.broadcasts.iter().find(|broadcast| {
+            matches!(broadcast.message.directed(push), Some(known) if known.get_name_type() == message.get_name_type())
+        })
+    }
+
+    /// Returns the names of the distinct message types, indexed by their ids.
+    pub fn message_names(&self) -> Vec<String> {
+        self.message_types().iter().map(|message| message.get_name_type()).collect()
+    }
+}
+
+// One end of a route, like Sensor: Reading <- Measuring
+pub struct RouteEnd {
+    pub machine: Ident,
+    pub message: Message,
+    pub targets: MessageTargets,
+}
+
+impl RouteEnd {
+    /// Returns the name of the parameter the state machine is passed to the pump function with
+    pub fn param_name(&self) -> Ident {
+        Ident::new(&self.machine.to_string().to_case(Case::Snake), self.machine.span())
+    }
+}
+
+// A route that moves the messages polled from one state machine into another one
+pub struct Route {
+    pub source: RouteEnd,
+    pub target: RouteEnd,
+}
+
+// Contains all routes that are pumped by the same function
+pub struct Routes {
+    pub visibility: Option<Visibility>,
+    pub name: Ident,
+    pub routes: Vec<Route>,
+}
+
+impl Routes {
+    /// Returns the distinct state machines of the routes in the order they first appear
+    pub fn machines(&self) -> Vec<&RouteEnd> {
+        let mut machines: Vec<&RouteEnd> = vec![];
+        for end in self.routes.iter().flat_map(|route| vec![&route.source, &route.target]) {
+            if !machines.iter().any(|known| known.machine == end.machine) {
+                machines.push(end);
+            }
+        }
+        machines
+    }
 }
 
 pub struct DeriveTransitionBase {
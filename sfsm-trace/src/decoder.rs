@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::fmt;
+use serde::Deserialize;
+use sfsm_base::trace::{TraceHook, TraceKind};
+
+/// The errors that can occur while decoding binary traces
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    /// A dictionary is not a valid JSON object
+    InvalidDictionary(String),
+    /// Two state machines with different names have the same id. One of them must be renamed.
+    IdCollision(u16),
+    /// A frame refers to a state machine that is not in the dictionary
+    UnknownMachine(u16),
+    /// A frame has an unknown kind. As the length of the frame is unknown, the decoder resyncs.
+    UnknownKind(u8),
+    /// The timestamp or the step of a frame is longer than a 64 or 32 bit varint. The decoder
+    /// resyncs.
+    InvalidVarint,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidDictionary(err) => write!(f, "Invalid dictionary: {}", err),
+            DecodeError::IdCollision(id) => write!(f, "Multiple state machines have the id {}", id),
+            DecodeError::UnknownMachine(id) => write!(f, "No state machine with the id {} in the dictionary", id),
+            DecodeError::UnknownKind(kind) => write!(f, "Unknown kind {}", kind),
+            DecodeError::InvalidVarint => write!(f, "Invalid timestamp or step"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// A dictionary as generated by the macros. The messages are defined apart from the state machine,
+/// so they come in a separate dictionary with the same id.
+#[derive(Deserialize)]
+struct Entry {
+    id: u16,
+    name: Option<String>,
+    states: Option<Vec<String>>,
+    messages: Option<Vec<String>>,
+}
+
+#[derive(Default, Debug)]
+struct Machine {
+    name: Option<String>,
+    states: Vec<String>,
+    messages: Vec<String>,
+}
+
+/// Holds the names of the state machines, their states and messages to turn the binary traces
+/// back into logs.
+///
+/// The state machines generate their dictionaries as ``` TRACE_DICTIONARY ``` and
+/// ``` TRACE_MESSAGES_DICTIONARY ``` constants if the trace-binary feature is enabled. They are JSON
+/// objects and can for example be written into a file by a test, one per line.
+/// ```rust
+/// # use sfsm_trace::Dictionary;
+/// let mut dictionary = Dictionary::new();
+/// dictionary.add(r#"{"id":1,"name":"Rocket","states":["WaitForLaunch","Launch"]}
+///                   {"id":1,"messages":["StartLaunch"]}"#).unwrap();
+/// assert_eq!(dictionary.machine_name(1), Some("Rocket"));
+/// assert_eq!(dictionary.state_name(1, 1), "Launch");
+/// assert_eq!(dictionary.message_name(1, 0), "StartLaunch");
+/// ```
+#[derive(Default, Debug)]
+pub struct Dictionary {
+    machines: HashMap<u16, Machine>,
+}
+
+impl Dictionary {
+    /// Creates an empty dictionary
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the dictionaries. Each non empty line must contain one dictionary.
+    pub fn add(&mut self, dictionaries: &str) -> Result<(), DecodeError> {
+        for line in dictionaries.lines().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+            let entry: Entry = serde_json::from_str(line)
+                .map_err(|err| DecodeError::InvalidDictionary(err.to_string()))?;
+            let machine = self.machines.entry(entry.id).or_default();
+            if let Some(name) = entry.name {
+                if machine.name.as_ref().is_some_and(|known| *known != name) {
+                    return Err(DecodeError::IdCollision(entry.id));
+                }
+                machine.name = Some(name);
+            }
+            if let Some(states) = entry.states {
+                machine.states = states;
+            }
+            if let Some(messages) = entry.messages {
+                machine.messages = messages;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the state machine with the id
+    pub fn machine_name(&self, machine: u16) -> Option<&str> {
+        self.machines.get(&machine).and_then(|machine| machine.name.as_deref())
+    }
+
+    /// Returns the name of a state or "?" if it is unknown
+    pub fn state_name(&self, machine: u16, state: u8) -> &str {
+        self.machines.get(&machine)
+            .and_then(|machine| machine.states.get(state as usize))
+            .map_or("?", |name| name.as_str())
+    }
+
+    /// Returns the name of a message or "?" if it is unknown
+    pub fn message_name(&self, machine: u16, message: u8) -> &str {
+        self.machines.get(&machine)
+            .and_then(|machine| machine.messages.get(message as usize))
+            .map_or("?", |name| name.as_str())
+    }
+
+    /// Formats the event the same way as the logs of the string based trace backends.
+    pub fn format(&self, event: &Event) -> Result<String, DecodeError> {
+        let log = self.format_log(event)?;
+        match (event.timestamp, event.step) {
+            (Some(timestamp), Some(step)) => Ok(format!("[{} #{}] {}", timestamp, step, log)),
+            _ => Ok(log),
+        }
+    }
+
+    fn format_log(&self, event: &Event) -> Result<String, DecodeError> {
+        let machine = event.machine;
+        let name = self.machine_name(machine).ok_or(DecodeError::UnknownMachine(machine))?;
+        let state = |index: usize| self.state_name(machine, event.ids[index]);
+        let log = match event.kind {
+            TraceKind::Stop => return Ok(format!("{}: {}", name, event.kind.name())),
+            TraceKind::Start | TraceKind::Enter | TraceKind::Exit | TraceKind::Execute => state(0).to_string(),
+            TraceKind::Transit => format!("From {} to {}", state(0), state(1)),
+            TraceKind::Push => format!("{} to {}", self.message_name(machine, event.ids[0]), state(1)),
+            TraceKind::Poll => format!("{} from {}", self.message_name(machine, event.ids[0]), state(1)),
+            TraceKind::Guard => format!("From {} to {}: {}", state(0), state(1),
+                                        if event.ids[2] == 1 { "Transit" } else { "Remain" }),
+            TraceKind::Error => format!("From {}, {} failed", state(0),
+                                        TraceHook::from_u8(event.ids[1]).map_or("?", |hook| hook.name())),
+        };
+        Ok(format!("{}: {} - {}", name, event.kind.name(), log))
+    }
+}
+
+/// A decoded trace point. The ids are the states, messages, guard results or hooks as described
+/// by the kind.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    /// The id of the state machine
+    pub machine: u16,
+    /// The kind of the trace point
+    pub kind: TraceKind,
+    /// The ids following the kind
+    pub ids: Vec<u8>,
+    /// The timestamp if the trace-time feature is enabled
+    pub timestamp: Option<u64>,
+    /// The number of steps the state machine had executed if the trace-time feature is enabled
+    pub step: Option<u32>,
+}
+
+/// Splits a stream of bytes into the frames of the trace points.
+///
+/// The bytes can be fed in chunks as they are received. Incomplete frames are kept until the rest of
+/// them arrives.
+///
+/// The frames do not contain a sync pattern. If bytes get lost or corrupted, a frame can have an
+/// unknown kind or an invalid varint. The decoder then resyncs: it drops the first byte of the
+/// frame and skips all following bytes until it finds the start of a frame with a known kind and,
+/// when decoding with a dictionary, a known state machine. The frames in between are lost and the
+/// first ones after a resync can still be wrong, as the bytes can look like the start of a frame
+/// by chance.
+/// ```rust
+/// # use sfsm_trace::{Decoder, Dictionary};
+/// let mut dictionary = Dictionary::new();
+/// dictionary.add(r#"{"id":1,"name":"Rocket","states":["WaitForLaunch","Launch"]}"#).unwrap();
+/// let mut decoder = Decoder::new();
+/// decoder.feed(&[1, 0, 4, 0]);
+/// assert!(decoder.next_event().is_none());
+/// decoder.feed(&[1]);
+/// let event = decoder.next_event().unwrap().unwrap();
+/// assert_eq!(dictionary.format(&event).unwrap(), "Rocket: Transit - From WaitForLaunch to Launch");
+///
+/// // With the trace-time feature, the timestamp 1000 and the step 3 follow
+/// decoder.feed(&[1, 0, 0x84, 0, 1, 0xE8, 0x07, 3]);
+/// let event = decoder.next_event().unwrap().unwrap();
+/// assert_eq!(dictionary.format(&event).unwrap(), "[1000 #3] Rocket: Transit - From WaitForLaunch to Launch");
+/// ```
+#[derive(Default, Debug)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+}
+
+impl Decoder {
+    /// Creates a decoder without any buffered bytes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffers the bytes until they are decoded
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Decodes the next complete frame if there is one. If the frame is invalid, the error is
+    /// returned and the decoder resyncs, so the next call continues with the following frame.
+    /// ```rust
+    /// # use sfsm_trace::{Decoder, DecodeError};
+    /// let mut decoder = Decoder::new();
+    /// // A transit with the time flag, whose timestamp does not end within ten bytes
+    /// decoder.feed(&[1, 0, 0x84, 0, 1]);
+    /// decoder.feed(&[0xFF; 10]);
+    /// assert_eq!(decoder.next_event(), Some(Err(DecodeError::InvalidVarint)));
+    /// ```
+    pub fn next_event(&mut self) -> Option<Result<Event, DecodeError>> {
+        self.next_event_of(None)
+    }
+
+    /// Decodes the next complete frame and resyncs to the next frame of a state machine in the
+    /// dictionary if the frame is invalid.
+    fn next_event_of(&mut self, dictionary: Option<&Dictionary>) -> Option<Result<Event, DecodeError>> {
+        match read_frame(&self.buffer) {
+            Ok(Some((event, len))) => {
+                self.buffer.drain(..len);
+                Some(Ok(event))
+            },
+            Ok(None) => None,
+            Err(err) => {
+                self.resync(dictionary);
+                Some(Err(err))
+            },
+        }
+    }
+
+    /// Feeds the bytes and formats all complete frames with the dictionary. Every frame has its
+    /// own result, so a frame that can not be decoded does not hide the logs of the other frames.
+    /// ```rust
+    /// # use sfsm_trace::{Decoder, DecodeError, Dictionary};
+    /// let mut dictionary = Dictionary::new();
+    /// dictionary.add(r#"{"id":1,"name":"Rocket","states":["WaitForLaunch","Launch"]}"#).unwrap();
+    /// let logs = Decoder::new().decode(&[1, 0, 4, 0, 1, 9, 9, 4, 0, 1, 1, 0, 4, 1, 0], &dictionary);
+    /// assert_eq!(logs, [
+    ///     Ok("Rocket: Transit - From WaitForLaunch to Launch".to_string()),
+    ///     Err(DecodeError::UnknownMachine(0x0909)),
+    ///     Ok("Rocket: Transit - From Launch to WaitForLaunch".to_string()),
+    /// ]);
+    ///
+    /// // The kind of the second frame is corrupted, so the decoder resyncs to the third one
+    /// let logs = Decoder::new().decode(&[1, 0, 4, 0, 1, 1, 0, 42, 0, 1, 0, 4, 1, 0], &dictionary);
+    /// assert_eq!(logs, [
+    ///     Ok("Rocket: Transit - From WaitForLaunch to Launch".to_string()),
+    ///     Err(DecodeError::UnknownKind(42)),
+    ///     Ok("Rocket: Transit - From Launch to WaitForLaunch".to_string()),
+    /// ]);
+    /// ```
+    pub fn decode(&mut self, bytes: &[u8], dictionary: &Dictionary) -> Vec<Result<String, DecodeError>> {
+        self.feed(bytes);
+        let mut logs = vec![];
+        while let Some(event) = self.next_event_of(Some(dictionary)) {
+            logs.push(event.and_then(|event| dictionary.format(&event)));
+        }
+        logs
+    }
+
+    /// Drops the first byte of the invalid frame and all following bytes up to the next one that
+    /// looks like the start of a frame. Bytes at the end that are too few to tell are kept.
+    fn resync(&mut self, dictionary: Option<&Dictionary>) {
+        let skip = (1..self.buffer.len())
+            .find(|start| match self.buffer.get(*start..*start + 3) {
+                Some(header) => {
+                    let machine = u16::from_le_bytes([header[0], header[1]]);
+                    TraceKind::from_u8(header[2] & !TraceKind::TIME_FLAG).is_some()
+                        && dictionary.is_none_or(|dictionary| dictionary.machines.contains_key(&machine))
+                },
+                None => true,
+            })
+            .unwrap_or(self.buffer.len());
+        self.buffer.drain(..skip);
+    }
+}
+
+/// Reads the frame at the start of the bytes. Returns the event and the length of the frame or
+/// None if the frame is incomplete.
+fn read_frame(bytes: &[u8]) -> Result<Option<(Event, usize)>, DecodeError> {
+    if bytes.len() < 3 {
+        return Ok(None);
+    }
+    let time = bytes[2] & TraceKind::TIME_FLAG != 0;
+    let kind = TraceKind::from_u8(bytes[2] & !TraceKind::TIME_FLAG)
+        .ok_or(DecodeError::UnknownKind(bytes[2]))?;
+    let mut len = 3 + kind.args();
+    if bytes.len() < len {
+        return Ok(None);
+    }
+    let mut timestamp = None;
+    let mut step = None;
+    if time {
+        let (value, value_len) = match read_varint(&bytes[len..], 64)? {
+            Some(varint) => varint,
+            None => return Ok(None),
+        };
+        timestamp = Some(value);
+        len += value_len;
+        let (value, value_len) = match read_varint(&bytes[len..], 32)? {
+            Some(varint) => varint,
+            None => return Ok(None),
+        };
+        step = Some(value as u32);
+        len += value_len;
+    }
+    Ok(Some((Event {
+        machine: u16::from_le_bytes([bytes[0], bytes[1]]),
+        kind,
+        ids: bytes[3..3 + kind.args()].to_vec(),
+        timestamp,
+        step,
+    }, len)))
+}
+
+/// Reads a LEB128 encoded variable length integer with at most the given number of bits. Returns
+/// the value and its length or None if it is incomplete.
+fn read_varint(bytes: &[u8], bits: u32) -> Result<Option<(u64, usize)>, DecodeError> {
+    let max_len = bits.div_ceil(7) as usize;
+    let mut value: u64 = 0;
+    for (index, byte) in bytes.iter().enumerate() {
+        let shift = 7 * index as u32;
+        let payload = (byte & 0x7F) as u64;
+        if index == max_len || (bits - shift < 7 && payload >> (bits - shift) != 0) {
+            return Err(DecodeError::InvalidVarint);
+        }
+        value |= payload << shift;
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, index + 1)));
+        }
+    }
+    Ok(None)
+}
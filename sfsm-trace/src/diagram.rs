@@ -0,0 +1,193 @@
+use sfsm_base::trace::{MachineInfo, TraceEvent, TraceRecord};
+
+/// The syntax a sequence diagram is rendered in
+#[derive(Clone, Copy, PartialEq)]
+enum Syntax {
+    PlantUml,
+    Mermaid,
+}
+
+/// Renders the records of the state machines as sequence diagram in the PlantUML or Mermaid
+/// syntax.
+///
+/// Every state machine instance is a participant. Transitions are drawn as arrows from the state machine to
+/// itself, errors as lost messages and starts and stops as notes. The messages are drawn between
+/// the state machines and the caller that pushed or polled them. The results of the guards are
+/// left out.
+/// ```rust
+/// # use sfsm_base::trace::{MachineInfo, MessageInfo, TraceRecord, TraceEvent};
+/// # use sfsm_trace::SequenceDiagram;
+/// static ROCKET: MachineInfo = MachineInfo { name: "Rocket", states: &["WaitForLaunch", "Launch"], error_state: None };
+/// static START_LAUNCH: MessageInfo = MessageInfo { id: 0, name: "StartLaunch" };
+///
+/// let mut diagram = SequenceDiagram::new();
+/// diagram.add(&TraceRecord { machine: &ROCKET, instance: 0, step: 0, timestamp: None, event: TraceEvent::Push { message: &START_LAUNCH, state: 0 } });
+/// diagram.add(&TraceRecord { machine: &ROCKET, instance: 0, step: 1, timestamp: None, event: TraceEvent::Transit { from: 0, to: 1 } });
+/// assert_eq!(diagram.mermaid(), "sequenceDiagram
+///     participant C as Caller
+///     participant M0 as Rocket
+///     C->>M0: StartLaunch to WaitForLaunch
+///     M0->>M0: WaitForLaunch to Launch
+/// ");
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct SequenceDiagram {
+    records: Vec<TraceRecord>,
+}
+
+impl SequenceDiagram {
+    /// Creates an empty diagram
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the record to the end of the diagram
+    pub fn add(&mut self, record: &TraceRecord) {
+        self.records.push(*record);
+    }
+
+    /// Renders the diagram in the PlantUML syntax
+    pub fn plantuml(&self) -> String {
+        self.render(Syntax::PlantUml)
+    }
+
+    /// Renders the diagram in the Mermaid syntax
+    pub fn mermaid(&self) -> String {
+        self.render(Syntax::Mermaid)
+    }
+
+    fn render(&self, syntax: Syntax) -> String {
+        let mut machines: Vec<(&'static MachineInfo, u16)> = vec![];
+        for record in &self.records {
+            if !machines.iter().any(|(machine, instance)| is_instance(record, machine, *instance)) {
+                machines.push((record.machine, record.instance));
+            }
+        }
+        let has_messages = self.records.iter().any(|record| {
+            matches!(record.event, TraceEvent::Push { .. } | TraceEvent::Poll { .. })
+        });
+
+        let mut lines = vec![];
+        if has_messages {
+            lines.push(participant(syntax, "C", "Caller"));
+        }
+        for (index, (machine, instance)) in machines.iter().enumerate() {
+            let name = if *instance == 0 {
+                machine.name.to_string()
+            } else {
+                format!("{}[{}]", machine.name, instance)
+            };
+            lines.push(participant(syntax, &format!("M{}", index), &name));
+        }
+
+        for record in &self.records {
+            let machine = record.machine;
+            let index = machines.iter()
+                .position(|(known, instance)| is_instance(record, known, *instance))
+                .unwrap_or_default();
+            let id = format!("M{}", index);
+            let time = match record.timestamp {
+                Some(timestamp) => format!("[{}] ", timestamp),
+                None => "".to_string(),
+            };
+            let line = match record.event {
+                TraceEvent::Start { state } => {
+                    note(syntax, &id, &format!("{}Start - {}", time, machine.state_name(state)))
+                },
+                TraceEvent::Stop { state } => {
+                    note(syntax, &id, &format!("{}Stop - {}", time, machine.state_name(state)))
+                },
+                TraceEvent::Transit { from, to } => {
+                    let label = format!("{}{} to {}", time, machine.state_name(from), machine.state_name(to));
+                    arrow(syntax, Arrow::Call, &id, &id, &label)
+                },
+                TraceEvent::Error { state, hook } => {
+                    let label = format!("{}{}, {} failed", time, machine.state_name(state), hook.name());
+                    arrow(syntax, Arrow::Lost, &id, &id, &label)
+                },
+                TraceEvent::Push { message, state } => {
+                    let label = format!("{}{} to {}", time, message.name, machine.state_name(state));
+                    arrow(syntax, Arrow::Call, "C", &id, &label)
+                },
+                TraceEvent::Poll { message, state } => {
+                    let label = format!("{}{} from {}", time, message.name, machine.state_name(state));
+                    arrow(syntax, Arrow::Reply, &id, "C", &label)
+                },
+                TraceEvent::Guard { .. } => continue,
+            };
+            lines.push(line);
+        }
+
+        match syntax {
+            Syntax::PlantUml => format!("@startuml\n{}\n@enduml\n", lines.join("\n")),
+            Syntax::Mermaid => {
+                let lines: Vec<String> = lines.iter().map(|line| format!("    {}\n", line)).collect();
+                format!("sequenceDiagram\n{}", lines.concat())
+            },
+        }
+    }
+}
+
+impl<'a> Extend<&'a TraceRecord> for SequenceDiagram {
+    fn extend<T: IntoIterator<Item = &'a TraceRecord>>(&mut self, records: T) {
+        for record in records {
+            self.add(record);
+        }
+    }
+}
+
+/// Checks if the record has been recorded by the instance of the state machine
+fn is_instance(record: &TraceRecord, machine: &MachineInfo, instance: u16) -> bool {
+    record.machine.name == machine.name && record.instance == instance
+}
+
+/// The kinds of arrows in the diagram
+enum Arrow {
+    Call,
+    Reply,
+    Lost,
+}
+
+fn participant(syntax: Syntax, id: &str, name: &str) -> String {
+    match syntax {
+        Syntax::PlantUml => format!("participant \"{}\" as {}", name, id),
+        Syntax::Mermaid => format!("participant {} as {}", id, escape(name)),
+    }
+}
+
+fn note(syntax: Syntax, id: &str, label: &str) -> String {
+    match syntax {
+        Syntax::PlantUml => format!("note over {} : {}", id, label),
+        Syntax::Mermaid => format!("Note over {}: {}", id, escape(label)),
+    }
+}
+
+fn arrow(syntax: Syntax, arrow: Arrow, from: &str, to: &str, label: &str) -> String {
+    match syntax {
+        Syntax::PlantUml => {
+            let arrow = match arrow {
+                Arrow::Call => "->",
+                Arrow::Reply => "-->",
+                Arrow::Lost => "->x",
+            };
+            format!("{} {} {} : {}", from, arrow, to, label)
+        },
+        Syntax::Mermaid => {
+            let arrow = match arrow {
+                Arrow::Call => "->>",
+                Arrow::Reply => "-->>",
+                Arrow::Lost => "-x",
+            };
+            format!("{}{}{}: {}", from, arrow, to, escape(label))
+        },
+    }
+}
+
+/// Replaces the characters Mermaid would interpret with their entity codes
+fn escape(text: &str) -> String {
+    text.chars().map(|character| match character {
+        '#' => "#35;".to_string(),
+        ';' => "#59;".to_string(),
+        character => character.to_string(),
+    }).collect()
+}
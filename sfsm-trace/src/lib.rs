@@ -0,0 +1,12 @@
+#![doc = include_str!("../README.md")]
+
+/// Contains the decoder for the binary traces of the trace-binary feature
+pub mod decoder;
+/// Contains the writer for the Value Change Dumps of the records of the trace-record feature
+pub mod vcd;
+/// Contains the renderer for the sequence diagrams of the records of the trace-record feature
+pub mod diagram;
+
+pub use decoder::*;
+pub use vcd::*;
+pub use diagram::*;
@@ -0,0 +1,190 @@
+use std::io::{self, Write};
+use sfsm_base::trace::{MachineInfo, TraceEvent, TraceRecord};
+
+/// Writes the records of the state machines as a Value Change Dump, which can be viewed next to
+/// other signals in waveform viewers like GTKWave.
+///
+/// Every state machine instance is a signal whose value is the id of its active state. Before it
+/// has been started and after it has been stopped, the value is unknown. The state machines must
+/// be added before the first record is written, as the signals are declared in the header of the
+/// dump. The records are written with their timestamp, so the trace-time feature must be enabled
+/// or the time must be passed with ``` record_at ```.
+/// ```rust
+/// # use sfsm_base::trace::{MachineInfo, TraceRecord, TraceEvent};
+/// # use sfsm_trace::VcdWriter;
+/// static ROCKET: MachineInfo = MachineInfo { name: "Rocket", states: &["WaitForLaunch", "Launch"], error_state: None };
+///
+/// let mut vcd = VcdWriter::new(Vec::new(), "1 ms");
+/// vcd.add_machine(&ROCKET).unwrap();
+/// vcd.record(&TraceRecord { machine: &ROCKET, instance: 0, step: 0, timestamp: Some(0), event: TraceEvent::Start { state: 0 } }).unwrap();
+/// vcd.record(&TraceRecord { machine: &ROCKET, instance: 0, step: 3, timestamp: Some(300), event: TraceEvent::Transit { from: 0, to: 1 } }).unwrap();
+/// let dump = String::from_utf8(vcd.into_inner()).unwrap();
+/// assert!(dump.contains("$var wire 8 ! Rocket $end"));
+/// assert!(dump.ends_with("#300\nb1 !\n"));
+/// ```
+pub struct VcdWriter<W: Write> {
+    out: W,
+    timescale: String,
+    signals: Vec<(&'static MachineInfo, u16)>,
+    started: bool,
+    time: Option<u64>,
+}
+
+impl<W: Write> VcdWriter<W> {
+    /// Creates a writer. The timescale is the unit of the timestamps, like "1 ms" or "10 us".
+    pub fn new(out: W, timescale: &str) -> Self {
+        Self {
+            out,
+            timescale: timescale.to_string(),
+            signals: vec![],
+            started: false,
+            time: None,
+        }
+    }
+
+    /// Adds a signal for the state machine. The ``` TRACE_INFO ``` constant of the state machine
+    /// describes it. The signal receives the records of the instance with the id 0, which is the
+    /// id of all instances whose id has not been set.
+    pub fn add_machine(&mut self, machine: &'static MachineInfo) -> io::Result<()> {
+        self.add_instance(machine, 0)
+    }
+
+    /// Adds a signal for the instance of the state machine with the id set with
+    /// ``` TraceControl::set_trace_instance ```. Instances other than 0 are named after the state
+    /// machine and their id, like ``` Rocket_2 ```.
+    /// ```rust
+    /// # use sfsm_base::trace::{MachineInfo, TraceRecord, TraceEvent};
+    /// # use sfsm_trace::VcdWriter;
+    /// static ROCKET: MachineInfo = MachineInfo { name: "Rocket", states: &["WaitForLaunch", "Launch"], error_state: None };
+    ///
+    /// let mut vcd = VcdWriter::new(Vec::new(), "1 ms");
+    /// vcd.add_instance(&ROCKET, 1).unwrap();
+    /// vcd.add_instance(&ROCKET, 2).unwrap();
+    /// vcd.record(&TraceRecord { machine: &ROCKET, instance: 2, step: 3, timestamp: Some(300), event: TraceEvent::Start { state: 1 } }).unwrap();
+    /// let dump = String::from_utf8(vcd.into_inner()).unwrap();
+    /// assert!(dump.contains("$var wire 8 ! Rocket_1 $end"));
+    /// assert!(dump.contains("$var wire 8 \" Rocket_2 $end"));
+    /// assert!(dump.ends_with("b1 \"\n"));
+    /// ```
+    pub fn add_instance(&mut self, machine: &'static MachineInfo, instance: u16) -> io::Result<()> {
+        if self.started {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "The state machines must be added before the first record is written"));
+        }
+        if !self.signals.iter().any(|(known, known_instance)| known.name == machine.name && *known_instance == instance) {
+            self.signals.push((machine, instance));
+        }
+        Ok(())
+    }
+
+    /// Writes the record at its timestamp.
+    pub fn record(&mut self, record: &TraceRecord) -> io::Result<()> {
+        match record.timestamp {
+            Some(timestamp) => self.record_at(timestamp, record),
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                       "The record has no timestamp. Enable the trace-time feature or use record_at")),
+        }
+    }
+
+    /// Writes the record at the given time. The time must not go backwards.
+    pub fn record_at(&mut self, time: u64, record: &TraceRecord) -> io::Result<()> {
+        let index = self.signals.iter()
+            .position(|(machine, instance)| machine.name == record.machine.name && *instance == record.instance)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                                          format!("The instance {} of the state machine {} has not been added",
+                                                  record.instance, record.machine.name)))?;
+        let value = match record.event {
+            TraceEvent::Start { state } => Some(state),
+            TraceEvent::Transit { to, .. } => Some(to),
+            TraceEvent::Error { .. } => match record.machine.error_state {
+                Some(error_state) => Some(error_state),
+                None => return Ok(()),
+            },
+            TraceEvent::Stop { .. } => None,
+            _ => return Ok(()),
+        };
+
+        if !self.started {
+            self.write_header(time)?;
+        }
+        match self.time {
+            Some(last) if time < last => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          format!("The time {} is before the previous time {}", time, last)));
+            },
+            Some(last) if time == last => {},
+            _ => {
+                writeln!(self.out, "#{}", time)?;
+                self.time = Some(time);
+            },
+        }
+        let id = identifier(index);
+        match value {
+            Some(state) => writeln!(self.out, "b{:b} {}", state, id),
+            None => writeln!(self.out, "bx {}", id),
+        }
+    }
+
+    /// Flushes the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Returns a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Declares the signals and sets them to unknown at the time of the first record. The names of
+    /// the states are listed in comments, as the dump only contains their ids.
+    fn write_header(&mut self, time: u64) -> io::Result<()> {
+        self.started = true;
+        writeln!(self.out, "$version sfsm-trace $end")?;
+        for (index, (machine, _)) in self.signals.iter().enumerate() {
+            if self.signals[..index].iter().any(|(known, _)| known.name == machine.name) {
+                continue;
+            }
+            let states: Vec<String> = machine.states.iter().enumerate()
+                .map(|(id, state)| format!("{} = {}", id, state)).collect();
+            writeln!(self.out, "$comment {}: {} $end", machine.name, states.join(", "))?;
+        }
+        writeln!(self.out, "$timescale {} $end", self.timescale)?;
+        writeln!(self.out, "$scope module sfsm $end")?;
+        for (index, (machine, instance)) in self.signals.iter().enumerate() {
+            if *instance == 0 {
+                writeln!(self.out, "$var wire 8 {} {} $end", identifier(index), machine.name)?;
+            } else {
+                writeln!(self.out, "$var wire 8 {} {}_{} $end", identifier(index), machine.name, instance)?;
+            }
+        }
+        writeln!(self.out, "$upscope $end")?;
+        writeln!(self.out, "$enddefinitions $end")?;
+        writeln!(self.out, "#{}", time)?;
+        writeln!(self.out, "$dumpvars")?;
+        for index in 0..self.signals.len() {
+            writeln!(self.out, "bx {}", identifier(index))?;
+        }
+        writeln!(self.out, "$end")?;
+        self.time = Some(time);
+        Ok(())
+    }
+}
+
+/// Returns the short identifier of the signal. It consists of the printable ASCII characters.
+fn identifier(index: usize) -> String {
+    let mut identifier = String::new();
+    let mut index = index;
+    loop {
+        identifier.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            return identifier;
+        }
+        index -= 1;
+    }
+}
@@ -5,3 +5,17 @@ extern crate sfsm_base;
 
 pub use sfsm_proc::*;
 pub use sfsm_base::*;
+
+#[doc(hidden)]
+pub use sfsm_proc::add_messages as __add_messages;
+
+/// Generates the code to push messages into and poll messages from the states of a state machine.
+/// See [sfsm_proc::add_messages] for the syntax. It passes the path of the sfsm crate on, so the
+/// messages broadcast to all states can request the states from the state machine even if the
+/// crate is renamed.
+#[macro_export]
+macro_rules! add_messages {
+    ($($definitions:tt)*) => {
+        $crate::__add_messages! { @callback $crate::add_messages; $($definitions)* }
+    };
+}
@@ -0,0 +1,17 @@
+// Checks the errors the macros report for invalid definitions. Update the expected errors with
+// TRYBUILD=overwrite cargo test --test ui
+#[cfg(not(feature = "trace-binary"))]
+#[test]
+fn ui() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
+
+// The binary traces change the generated code, so their errors are checked on their own with
+// TRYBUILD=overwrite cargo test --test ui --features="trace-binary"
+#[cfg(feature = "trace-binary")]
+#[test]
+fn ui_trace_binary() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/trace_binary/*.rs");
+}
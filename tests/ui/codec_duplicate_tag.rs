@@ -0,0 +1,31 @@
+use sfsm::*;
+
+pub struct WaitForLaunch {}
+pub struct Launch {}
+pub struct StartLaunch {}
+pub struct Abort {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+add_messages!(
+    #[sfsm(codec)]
+    Rocket,
+    [
+        #[sfsm(tag = 3)] StartLaunch -> WaitForLaunch,
+        #[sfsm(tag = 3)] Abort -> Launch,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+derive_transition_into!(WaitForLaunch, Launch);
+derive_transition!(WaitForLaunch, Launch, TransitGuard::Transit);
+
+fn main() {}
@@ -0,0 +1,62 @@
+use sfsm::*;
+
+pub struct WaitForLaunch {}
+pub struct Launch {}
+pub struct M<const N: usize> {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+// A tag is a single byte, so there is no tag left for the 257th message
+add_messages!(
+    #[sfsm(codec)]
+    Rocket,
+    [
+        M<0> -> WaitForLaunch, M<1> -> WaitForLaunch, M<2> -> WaitForLaunch, M<3> -> WaitForLaunch, M<4> -> WaitForLaunch, M<5> -> WaitForLaunch, M<6> -> WaitForLaunch, M<7> -> WaitForLaunch,
+        M<8> -> WaitForLaunch, M<9> -> WaitForLaunch, M<10> -> WaitForLaunch, M<11> -> WaitForLaunch, M<12> -> WaitForLaunch, M<13> -> WaitForLaunch, M<14> -> WaitForLaunch, M<15> -> WaitForLaunch,
+        M<16> -> WaitForLaunch, M<17> -> WaitForLaunch, M<18> -> WaitForLaunch, M<19> -> WaitForLaunch, M<20> -> WaitForLaunch, M<21> -> WaitForLaunch, M<22> -> WaitForLaunch, M<23> -> WaitForLaunch,
+        M<24> -> WaitForLaunch, M<25> -> WaitForLaunch, M<26> -> WaitForLaunch, M<27> -> WaitForLaunch, M<28> -> WaitForLaunch, M<29> -> WaitForLaunch, M<30> -> WaitForLaunch, M<31> -> WaitForLaunch,
+        M<32> -> WaitForLaunch, M<33> -> WaitForLaunch, M<34> -> WaitForLaunch, M<35> -> WaitForLaunch, M<36> -> WaitForLaunch, M<37> -> WaitForLaunch, M<38> -> WaitForLaunch, M<39> -> WaitForLaunch,
+        M<40> -> WaitForLaunch, M<41> -> WaitForLaunch, M<42> -> WaitForLaunch, M<43> -> WaitForLaunch, M<44> -> WaitForLaunch, M<45> -> WaitForLaunch, M<46> -> WaitForLaunch, M<47> -> WaitForLaunch,
+        M<48> -> WaitForLaunch, M<49> -> WaitForLaunch, M<50> -> WaitForLaunch, M<51> -> WaitForLaunch, M<52> -> WaitForLaunch, M<53> -> WaitForLaunch, M<54> -> WaitForLaunch, M<55> -> WaitForLaunch,
+        M<56> -> WaitForLaunch, M<57> -> WaitForLaunch, M<58> -> WaitForLaunch, M<59> -> WaitForLaunch, M<60> -> WaitForLaunch, M<61> -> WaitForLaunch, M<62> -> WaitForLaunch, M<63> -> WaitForLaunch,
+        M<64> -> WaitForLaunch, M<65> -> WaitForLaunch, M<66> -> WaitForLaunch, M<67> -> WaitForLaunch, M<68> -> WaitForLaunch, M<69> -> WaitForLaunch, M<70> -> WaitForLaunch, M<71> -> WaitForLaunch,
+        M<72> -> WaitForLaunch, M<73> -> WaitForLaunch, M<74> -> WaitForLaunch, M<75> -> WaitForLaunch, M<76> -> WaitForLaunch, M<77> -> WaitForLaunch, M<78> -> WaitForLaunch, M<79> -> WaitForLaunch,
+        M<80> -> WaitForLaunch, M<81> -> WaitForLaunch, M<82> -> WaitForLaunch, M<83> -> WaitForLaunch, M<84> -> WaitForLaunch, M<85> -> WaitForLaunch, M<86> -> WaitForLaunch, M<87> -> WaitForLaunch,
+        M<88> -> WaitForLaunch, M<89> -> WaitForLaunch, M<90> -> WaitForLaunch, M<91> -> WaitForLaunch, M<92> -> WaitForLaunch, M<93> -> WaitForLaunch, M<94> -> WaitForLaunch, M<95> -> WaitForLaunch,
+        M<96> -> WaitForLaunch, M<97> -> WaitForLaunch, M<98> -> WaitForLaunch, M<99> -> WaitForLaunch, M<100> -> WaitForLaunch, M<101> -> WaitForLaunch, M<102> -> WaitForLaunch, M<103> -> WaitForLaunch,
+        M<104> -> WaitForLaunch, M<105> -> WaitForLaunch, M<106> -> WaitForLaunch, M<107> -> WaitForLaunch, M<108> -> WaitForLaunch, M<109> -> WaitForLaunch, M<110> -> WaitForLaunch, M<111> -> WaitForLaunch,
+        M<112> -> WaitForLaunch, M<113> -> WaitForLaunch, M<114> -> WaitForLaunch, M<115> -> WaitForLaunch, M<116> -> WaitForLaunch, M<117> -> WaitForLaunch, M<118> -> WaitForLaunch, M<119> -> WaitForLaunch,
+        M<120> -> WaitForLaunch, M<121> -> WaitForLaunch, M<122> -> WaitForLaunch, M<123> -> WaitForLaunch, M<124> -> WaitForLaunch, M<125> -> WaitForLaunch, M<126> -> WaitForLaunch, M<127> -> WaitForLaunch,
+        M<128> -> WaitForLaunch, M<129> -> WaitForLaunch, M<130> -> WaitForLaunch, M<131> -> WaitForLaunch, M<132> -> WaitForLaunch, M<133> -> WaitForLaunch, M<134> -> WaitForLaunch, M<135> -> WaitForLaunch,
+        M<136> -> WaitForLaunch, M<137> -> WaitForLaunch, M<138> -> WaitForLaunch, M<139> -> WaitForLaunch, M<140> -> WaitForLaunch, M<141> -> WaitForLaunch, M<142> -> WaitForLaunch, M<143> -> WaitForLaunch,
+        M<144> -> WaitForLaunch, M<145> -> WaitForLaunch, M<146> -> WaitForLaunch, M<147> -> WaitForLaunch, M<148> -> WaitForLaunch, M<149> -> WaitForLaunch, M<150> -> WaitForLaunch, M<151> -> WaitForLaunch,
+        M<152> -> WaitForLaunch, M<153> -> WaitForLaunch, M<154> -> WaitForLaunch, M<155> -> WaitForLaunch, M<156> -> WaitForLaunch, M<157> -> WaitForLaunch, M<158> -> WaitForLaunch, M<159> -> WaitForLaunch,
+        M<160> -> WaitForLaunch, M<161> -> WaitForLaunch, M<162> -> WaitForLaunch, M<163> -> WaitForLaunch, M<164> -> WaitForLaunch, M<165> -> WaitForLaunch, M<166> -> WaitForLaunch, M<167> -> WaitForLaunch,
+        M<168> -> WaitForLaunch, M<169> -> WaitForLaunch, M<170> -> WaitForLaunch, M<171> -> WaitForLaunch, M<172> -> WaitForLaunch, M<173> -> WaitForLaunch, M<174> -> WaitForLaunch, M<175> -> WaitForLaunch,
+        M<176> -> WaitForLaunch, M<177> -> WaitForLaunch, M<178> -> WaitForLaunch, M<179> -> WaitForLaunch, M<180> -> WaitForLaunch, M<181> -> WaitForLaunch, M<182> -> WaitForLaunch, M<183> -> WaitForLaunch,
+        M<184> -> WaitForLaunch, M<185> -> WaitForLaunch, M<186> -> WaitForLaunch, M<187> -> WaitForLaunch, M<188> -> WaitForLaunch, M<189> -> WaitForLaunch, M<190> -> WaitForLaunch, M<191> -> WaitForLaunch,
+        M<192> -> WaitForLaunch, M<193> -> WaitForLaunch, M<194> -> WaitForLaunch, M<195> -> WaitForLaunch, M<196> -> WaitForLaunch, M<197> -> WaitForLaunch, M<198> -> WaitForLaunch, M<199> -> WaitForLaunch,
+        M<200> -> WaitForLaunch, M<201> -> WaitForLaunch, M<202> -> WaitForLaunch, M<203> -> WaitForLaunch, M<204> -> WaitForLaunch, M<205> -> WaitForLaunch, M<206> -> WaitForLaunch, M<207> -> WaitForLaunch,
+        M<208> -> WaitForLaunch, M<209> -> WaitForLaunch, M<210> -> WaitForLaunch, M<211> -> WaitForLaunch, M<212> -> WaitForLaunch, M<213> -> WaitForLaunch, M<214> -> WaitForLaunch, M<215> -> WaitForLaunch,
+        M<216> -> WaitForLaunch, M<217> -> WaitForLaunch, M<218> -> WaitForLaunch, M<219> -> WaitForLaunch, M<220> -> WaitForLaunch, M<221> -> WaitForLaunch, M<222> -> WaitForLaunch, M<223> -> WaitForLaunch,
+        M<224> -> WaitForLaunch, M<225> -> WaitForLaunch, M<226> -> WaitForLaunch, M<227> -> WaitForLaunch, M<228> -> WaitForLaunch, M<229> -> WaitForLaunch, M<230> -> WaitForLaunch, M<231> -> WaitForLaunch,
+        M<232> -> WaitForLaunch, M<233> -> WaitForLaunch, M<234> -> WaitForLaunch, M<235> -> WaitForLaunch, M<236> -> WaitForLaunch, M<237> -> WaitForLaunch, M<238> -> WaitForLaunch, M<239> -> WaitForLaunch,
+        M<240> -> WaitForLaunch, M<241> -> WaitForLaunch, M<242> -> WaitForLaunch, M<243> -> WaitForLaunch, M<244> -> WaitForLaunch, M<245> -> WaitForLaunch, M<246> -> WaitForLaunch, M<247> -> WaitForLaunch,
+        M<248> -> WaitForLaunch, M<249> -> WaitForLaunch, M<250> -> WaitForLaunch, M<251> -> WaitForLaunch, M<252> -> WaitForLaunch, M<253> -> WaitForLaunch, M<254> -> WaitForLaunch, M<255> -> WaitForLaunch,
+        M<256> -> WaitForLaunch,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+derive_transition_into!(WaitForLaunch, Launch);
+derive_transition!(WaitForLaunch, Launch, TransitGuard::Transit);
+
+fn main() {}
@@ -0,0 +1,41 @@
+use sfsm::*;
+
+pub struct WaitForLaunch {}
+pub struct Launch {}
+pub struct Command<T> {
+    pub value: T,
+}
+pub struct CommandU8 {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+add_messages!(
+    #[sfsm(dispatch)]
+    Rocket,
+    [
+        Command<u8> -> WaitForLaunch,
+        CommandU8 -> WaitForLaunch,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+derive_transition_into!(WaitForLaunch, Launch);
+derive_transition!(WaitForLaunch, Launch, TransitGuard::Transit);
+
+impl ReceiveMessage<Command<u8>> for WaitForLaunch {
+    fn receive_message(&mut self, _message: Command<u8>) {}
+}
+
+impl ReceiveMessage<CommandU8> for WaitForLaunch {
+    fn receive_message(&mut self, _message: CommandU8) {}
+}
+
+fn main() {}
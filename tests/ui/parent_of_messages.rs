@@ -0,0 +1,41 @@
+use sfsm::*;
+
+pub struct WaitForLaunch {}
+pub struct Launch {}
+pub struct StartLaunch {}
+
+add_state_machine!(
+    MissionControl,
+    WaitForLaunch,
+    [WaitForLaunch],
+    []
+);
+
+add_state_machine!(
+    #[sfsm(parent = "MissionControl")]
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+add_messages!(
+    #[sfsm(parent = "GroundControl")]
+    Rocket,
+    [
+        StartLaunch -> *,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+derive_transition_into!(WaitForLaunch, Launch);
+derive_transition!(WaitForLaunch, Launch, TransitGuard::Transit);
+
+impl ReceiveMessage<StartLaunch> for WaitForLaunch {
+    fn receive_message(&mut self, _message: StartLaunch) {}
+}
+
+fn main() {}
@@ -0,0 +1,29 @@
+use sfsm::*;
+
+pub struct Idle {}
+
+// Both names fold to the trace id 47915
+add_state_machine!(
+    MotorHeater,
+    Idle,
+    [Idle],
+    []
+);
+
+mod brake {
+    use super::*;
+
+    add_state_machine!(
+        MotorBrake,
+        Idle,
+        [Idle],
+        []
+    );
+}
+
+derive_state!(Idle);
+
+#[sfsm_trace_binary]
+fn trace(_frame: &[u8]) {}
+
+fn main() {}
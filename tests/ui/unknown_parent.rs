@@ -0,0 +1,21 @@
+use sfsm::*;
+
+pub struct WaitForLaunch {}
+pub struct Launch {}
+
+add_state_machine!(
+    #[sfsm(parent = "MissionControl")]
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+derive_transition_into!(WaitForLaunch, Launch);
+derive_transition!(WaitForLaunch, Launch, TransitGuard::Transit);
+
+fn main() {}
@@ -0,0 +1,24 @@
+use sfsm::*;
+
+pub struct WaitForLaunch {}
+pub struct Launch {}
+pub struct StartLaunch {}
+
+add_state_machine!(
+    Rocket,
+    WaitForLaunch,
+    [WaitForLaunch, Launch],
+    [
+        WaitForLaunch => Launch,
+    ],
+    [
+        StartLaunch -> Land,
+    ]
+);
+
+derive_state!(WaitForLaunch);
+derive_state!(Launch);
+derive_transition_into!(WaitForLaunch, Launch);
+derive_transition!(WaitForLaunch, Launch, TransitGuard::Transit);
+
+fn main() {}